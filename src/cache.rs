@@ -0,0 +1,66 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use jiff::civil::Date;
+
+use crate::puzzle::Variant;
+
+/// Downloaded puzzle JSON, stored in the user's cache directory by publication date.
+pub struct Cache {
+    dir: PathBuf,
+    variant: Variant,
+}
+
+impl Cache {
+    /// The cache for one of the crosswords.
+    pub fn open(variant: Variant) -> Option<Self> {
+        let dir = dirs::cache_dir()?.join("miniprint");
+        Some(Cache { dir, variant })
+    }
+
+    fn path(&self, date: Date) -> PathBuf {
+        self.dir
+            .join(format!("{}-{date}.json", self.variant.name()))
+    }
+
+    pub fn get(&self, date: Date) -> io::Result<Option<String>> {
+        match fs::read_to_string(self.path(date)) {
+            Ok(json) => Ok(Some(json)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The most recently published puzzle in the cache.
+    pub fn latest(&self) -> io::Result<Option<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut latest = None;
+        for entry in entries {
+            let name = entry?.file_name();
+            let date = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(self.variant.name())?.strip_prefix('-'))
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|date| date.parse::<Date>().ok());
+            if date > latest {
+                latest = date;
+            }
+        }
+        latest.map_or(Ok(None), |date| self.get(date))
+    }
+
+    pub fn put(&self, date: Date, json: &str) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        // write to a temporary file first so a reader never sees half a puzzle
+        let tmp = self
+            .dir
+            .join(format!(".{}-{date}.json.tmp", self.variant.name()));
+        fs::write(&tmp, json)?;
+        fs::rename(tmp, self.path(date))
+    }
+}
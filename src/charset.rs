@@ -0,0 +1,399 @@
+//! Getting clue text onto a printer that only knows 8-bit code pages.
+//!
+//! Each receipt uses whichever of the printer's code pages covers the most of
+//! its characters. Anything else is spelled out in ASCII where there's an
+//! obvious way to (`“` as `"`, `é` as `e`), or else drawn as a user-defined
+//! character if there's a font for it, and printed as `?` as a last resort.
+
+use std::collections::BTreeSet;
+
+use clap::ValueEnum;
+use escpos::driver::Driver;
+use escpos::printer::Printer;
+use escpos::utils::PageCode;
+use resvg::{tiny_skia, usvg};
+use serde::Deserialize;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::fonts;
+
+/// ESC & and ESC %: define and select user-defined characters
+const DEFINE_CHARACTERS: &[u8] = b"\x1b&";
+const USER_CHARACTERS: &[u8] = b"\x1b%";
+
+/// The ASCII characters that can be given up for a drawn glyph, as long as
+/// the receipt doesn't use them itself.
+const SPARE_CODES: &[u8] = b"~`|{}\\";
+
+/// A private use character that no font should have.
+const NOT_A_CHARACTER: char = '\u{10fffd}';
+
+/// The upper halves of the code pages we know how to encode to, with `\0`
+/// for the unused positions.
+#[rustfmt::skip]
+const PC437: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+#[rustfmt::skip]
+const PC850: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', 'ø', '£', 'Ø', '×', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '®', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', 'Á', 'Â', 'À', '©', '╣', '║', '╗', '╝', '¢', '¥', '┐',
+    '└', '┴', '┬', '├', '─', '┼', 'ã', 'Ã', '╚', '╔', '╩', '╦', '╠', '═', '╬', '¤',
+    'ð', 'Ð', 'Ê', 'Ë', 'È', 'ı', 'Í', 'Î', 'Ï', '┘', '┌', '█', '▄', '¦', 'Ì', '▀',
+    'Ó', 'ß', 'Ô', 'Ò', 'õ', 'Õ', 'µ', 'þ', 'Þ', 'Ú', 'Û', 'Ù', 'ý', 'Ý', '¯', '´',
+    '\u{ad}', '±', '‗', '¾', '¶', '§', '÷', '¸', '°', '¨', '·', '¹', '³', '²', '■', '\u{a0}',
+];
+
+#[rustfmt::skip]
+const WPC1252: [char; 128] = [
+    '€', '\0', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\0', 'Ž', '\0',
+    '\0', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\0', 'ž', 'Ÿ',
+    '\u{a0}', '¡', '¢', '£', '¤', '¥', '¦', '§', '¨', '©', 'ª', '«', '¬', '\u{ad}', '®', '¯',
+    '°', '±', '²', '³', '´', 'µ', '¶', '·', '¸', '¹', 'º', '»', '¼', '½', '¾', '¿',
+    'À', 'Á', 'Â', 'Ã', 'Ä', 'Å', 'Æ', 'Ç', 'È', 'É', 'Ê', 'Ë', 'Ì', 'Í', 'Î', 'Ï',
+    'Ð', 'Ñ', 'Ò', 'Ó', 'Ô', 'Õ', 'Ö', '×', 'Ø', 'Ù', 'Ú', 'Û', 'Ü', 'Ý', 'Þ', 'ß',
+    'à', 'á', 'â', 'ã', 'ä', 'å', 'æ', 'ç', 'è', 'é', 'ê', 'ë', 'ì', 'í', 'î', 'ï',
+    'ð', 'ñ', 'ò', 'ó', 'ô', 'õ', 'ö', '÷', 'ø', 'ù', 'ú', 'û', 'ü', 'ý', 'þ', 'ÿ',
+];
+
+/// A character table the printer can switch to with ESC t.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CodePage {
+    /// The original IBM PC set, which printers start up in
+    #[default]
+    Pc437,
+    /// Western European DOS
+    Pc850,
+    /// PC850 with the euro sign
+    Pc858,
+    /// Windows Western European, which has curly quotes and dashes
+    Wpc1252,
+}
+
+impl CodePage {
+    fn byte(self, c: char) -> Option<u8> {
+        let table = match self {
+            CodePage::Pc437 => &PC437,
+            CodePage::Pc850 | CodePage::Pc858 => &PC850,
+            CodePage::Wpc1252 => &WPC1252,
+        };
+        match (self, c) {
+            (_, '\0') => None,
+            (CodePage::Pc858, '€') => Some(0xd5),
+            (CodePage::Pc858, 'ı') => None,
+            _ => table.iter().position(|&t| t == c).map(|i| 0x80 + i as u8),
+        }
+    }
+
+    /// The code page ESC t `n` selects, if it's one we know.
+    pub fn from_number(n: u8) -> Option<CodePage> {
+        CodePage::value_variants()
+            .iter()
+            .copied()
+            .find(|&page| u8::from(PageCode::from(page)) == n)
+    }
+
+    /// What the printer prints for `byte`.
+    pub fn char(self, byte: u8) -> Option<char> {
+        let table = match self {
+            CodePage::Pc437 => &PC437,
+            CodePage::Pc850 => &PC850,
+            CodePage::Pc858 if byte == 0xd5 => return Some('€'),
+            CodePage::Pc858 => &PC850,
+            CodePage::Wpc1252 => &WPC1252,
+        };
+        match byte {
+            0x20..=0x7e => Some(char::from(byte)),
+            0x80.. => Some(table[usize::from(byte - 0x80)]).filter(|&c| c != '\0'),
+            _ => None,
+        }
+    }
+}
+
+impl From<CodePage> for PageCode {
+    fn from(page: CodePage) -> PageCode {
+        match page {
+            CodePage::Pc437 => PageCode::PC437,
+            CodePage::Pc850 => PageCode::PC850,
+            CodePage::Pc858 => PageCode::PC858,
+            CodePage::Wpc1252 => PageCode::WPC1252,
+        }
+    }
+}
+
+/// How the text of one receipt gets encoded.
+pub struct Charset {
+    page: CodePage,
+    /// Whether any character actually comes from the code page, so that it
+    /// needs selecting.
+    uses_page: bool,
+    /// Characters drawn as user-defined characters, with the ASCII code they
+    /// replace and their bitmap.
+    glyphs: Vec<(char, u8, Vec<u8>)>,
+    glyph_width: u8,
+}
+
+impl Charset {
+    /// Pick the best of `pages` for `text`, which should be everything that
+    /// will be printed. With a `glyph_width`, characters that can't be
+    /// printed otherwise are drawn that many dots wide.
+    pub fn new(pages: &[CodePage], glyph_width: Option<u8>, text: &str) -> Charset {
+        let special = text
+            .chars()
+            .filter(|c| !c.is_ascii())
+            .collect::<BTreeSet<_>>();
+        // ties go to the page listed first
+        let page = pages
+            .iter()
+            .rev()
+            .max_by_key(|page| special.iter().filter(|&&c| page.byte(c).is_some()).count())
+            .copied()
+            .unwrap_or_default();
+
+        let mut charset = Charset {
+            page,
+            uses_page: special.iter().any(|&c| page.byte(c).is_some()),
+            glyphs: Vec::new(),
+            glyph_width: glyph_width.unwrap_or(0),
+        };
+        let Some(width) = glyph_width else {
+            return charset;
+        };
+
+        let missing = special
+            .into_iter()
+            .filter(|&c| page.byte(c).is_none() && transliterate(c).is_none())
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return charset;
+        }
+        let used = charset.prepare(text);
+        let mut spare = SPARE_CODES
+            .iter()
+            .filter(|&&code| !used.contains(char::from(code)));
+        let mut opt = usvg::Options::default();
+        fonts::load(&mut opt);
+        // what fonts draw for characters they don't have, usually a box
+        let notdef = render_glyph(NOT_A_CHARACTER, width, &opt);
+        for c in missing {
+            if let Some(bitmap) =
+                render_glyph(c, width, &opt).filter(|b| Some(b) != notdef.as_ref())
+            {
+                let Some(&code) = spare.next() else { break };
+                charset.glyphs.push((c, code, bitmap));
+            }
+        }
+        charset
+    }
+
+    /// Replace every character that isn't in the code page with what will
+    /// be printed in its place, so each character is one column wide.
+    pub fn prepare(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        for c in text.chars() {
+            if c.is_ascii() || self.page.byte(c).is_some() {
+                out.push(c);
+            } else if let Some(&(_, code, _)) = self.glyphs.iter().find(|(g, ..)| *g == c) {
+                out.push(char::from(code));
+            } else if let Some(ascii) = transliterate(c) {
+                out.push_str(&ascii);
+            } else {
+                out.push('?');
+            }
+        }
+        out
+    }
+
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        self.prepare(text)
+            .chars()
+            .map(|c| match u8::try_from(c) {
+                Ok(b) if b.is_ascii() => b,
+                _ => self.page.byte(c).unwrap_or(b'?'),
+            })
+            .collect()
+    }
+
+    pub fn write<D: Driver>(
+        &self,
+        printer: &mut Printer<D>,
+        text: &str,
+    ) -> escpos::errors::Result<()> {
+        printer.custom(&self.encode(text))?;
+        Ok(())
+    }
+
+    pub fn writeln<D: Driver>(
+        &self,
+        printer: &mut Printer<D>,
+        text: &str,
+    ) -> escpos::errors::Result<()> {
+        self.write(printer, text)?;
+        printer.feed()?;
+        Ok(())
+    }
+
+    /// Switch to the code page and send the drawn characters, if this
+    /// receipt needs them.
+    pub fn select<D: Driver>(&self, printer: &mut Printer<D>) -> escpos::errors::Result<()> {
+        if self.uses_page {
+            printer.page_code(self.page.into())?;
+        }
+        if self.glyphs.is_empty() {
+            return Ok(());
+        }
+        let rows = self.glyph_width.div_ceil(4);
+        for (_, code, bitmap) in &self.glyphs {
+            let mut cmd = DEFINE_CHARACTERS.to_vec();
+            cmd.extend([rows, *code, *code, self.glyph_width]);
+            cmd.extend(bitmap);
+            printer.custom(&cmd)?;
+        }
+        printer.custom(&[USER_CHARACTERS, &[1]].concat())?;
+        Ok(())
+    }
+
+    /// Put the printer back to its own characters and the code page it
+    /// starts up in, for whatever prints next.
+    pub fn deselect<D: Driver>(&self, printer: &mut Printer<D>) -> escpos::errors::Result<()> {
+        if !self.glyphs.is_empty() {
+            printer.custom(&[USER_CHARACTERS, &[0]].concat())?;
+        }
+        if self.uses_page {
+            printer.page_code(CodePage::default().into())?;
+        }
+        Ok(())
+    }
+}
+
+/// An ASCII stand-in for a character, if there's an obvious one.
+fn transliterate(c: char) -> Option<String> {
+    let s = match c {
+        '‘' | '’' | '‚' | '‛' | '′' | 'ʼ' => "'",
+        '“' | '”' | '„' | '‟' | '″' | '«' | '»' => "\"",
+        '‐' | '‑' | '‒' | '–' | '−' | '\u{ad}' => "-",
+        '—' | '―' => "--",
+        '…' => "...",
+        '•' | '·' | '∙' => "*",
+        '×' => "x",
+        '÷' => "/",
+        '½' => "1/2",
+        '¼' => "1/4",
+        '¾' => "3/4",
+        '€' => "EUR",
+        '™' => "TM",
+        '©' => "(c)",
+        '®' => "(R)",
+        'ß' => "ss",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ø' => "o",
+        'Ø' => "O",
+        'ł' => "l",
+        'Ł' => "L",
+        'đ' | 'ð' => "d",
+        'Đ' | 'Ð' => "D",
+        'þ' => "th",
+        'Þ' => "Th",
+        'ı' => "i",
+        _ if c.is_whitespace() => " ",
+        // accented letters, without their accents
+        _ => {
+            let base = c
+                .nfd()
+                .filter(|&c| !is_combining_mark(c))
+                .collect::<String>();
+            return (!base.is_empty() && base.is_ascii()).then_some(base);
+        }
+    };
+    Some(s.to_owned())
+}
+
+/// Draw `c` for ESC &: a column at a time from the left, each column top to
+/// bottom with the top dot in the high bit. Characters are twice as tall as
+/// they are wide, like the printer's own font.
+fn render_glyph(c: char, width: u8, opt: &usvg::Options) -> Option<Vec<u8>> {
+    let (width, height) = (u32::from(width), u32::from(width) * 2);
+    let svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}">
+            <text x="{x}" y="{y}" font-size="{size}" text-anchor="middle">&#x{code:x};</text>
+        </svg>"#,
+        x = width as f32 / 2.0,
+        y = height as f32 * 0.8,
+        size = width as f32 * 1.2,
+        code = u32::from(c),
+    );
+    let tree = usvg::Tree::from_str(&svg, opt).ok()?;
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::identity(),
+        &mut pixmap.as_mut(),
+    );
+
+    let mut bitmap = Vec::new();
+    for x in 0..width {
+        for row in 0..height.div_ceil(8) {
+            let mut byte = 0;
+            for bit in 0..8 {
+                let y = row * 8 + bit;
+                let dot = pixmap.pixel(x, y).is_some_and(|p| p.alpha() >= 64);
+                byte |= u8::from(dot) << (7 - bit);
+            }
+            bitmap.push(byte);
+        }
+    }
+    Some(bitmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLUE: &str = "Like some “fine” prints — or dashes, per Molière";
+
+    #[test]
+    fn transliterates_what_the_page_lacks() {
+        let charset = Charset::new(&[CodePage::Pc437], None, CLUE);
+        assert_eq!(
+            charset.prepare(CLUE),
+            "Like some \"fine\" prints -- or dashes, per Molière"
+        );
+        assert_eq!(charset.encode("è"), [0x8a]);
+        assert_eq!(charset.encode("Ω☃"), [0xea, b'?']);
+
+        let ascii = Charset::new(&[CodePage::Pc850], None, "Crêpe");
+        assert_eq!(ascii.encode("Ωñ"), b"?\xa4");
+    }
+
+    #[test]
+    fn picks_the_page_covering_the_most() {
+        let pages = [CodePage::Pc437, CodePage::Pc858, CodePage::Wpc1252];
+        let charset = Charset::new(&pages, None, CLUE);
+        assert_eq!(charset.page, CodePage::Wpc1252);
+        assert_eq!(charset.encode("“—”"), [0x93, 0x97, 0x94]);
+
+        // nothing to gain from switching, so stay on the first
+        let charset = Charset::new(&pages, None, "Plain");
+        assert_eq!(charset.page, CodePage::Pc437);
+        assert!(!charset.uses_page);
+
+        let charset = Charset::new(&pages, None, "5€");
+        assert_eq!(charset.page, CodePage::Pc858);
+        assert_eq!(charset.encode("€"), [0xd5]);
+    }
+}
@@ -0,0 +1,281 @@
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::{Args, Parser, Subcommand};
+use jiff::civil::Date;
+use jiff::{tz::TimeZone, Timestamp};
+
+use crate::charset::CodePage;
+use crate::config::{Cut, FlowControl, GridRenderer, Italics, Section};
+use crate::driver::Target;
+use crate::error::EXIT_STATUS;
+use crate::paper::Paper;
+use crate::puzzle::Variant;
+
+/// Print the NYT crosswords on an ESC/POS receipt printer.
+#[derive(Parser)]
+#[command(
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    after_long_help = EXIT_STATUS
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Config file to read instead of ~/.config/miniprint/config.toml
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub print: PrintArgs,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Fetch a puzzle and print it (the default when no subcommand is given)
+    Print(PrintArgs),
+    /// Download the puzzle JSON without printing it
+    Fetch(FetchArgs),
+}
+
+#[derive(Args)]
+pub struct PrintArgs {
+    #[command(flatten)]
+    pub puzzle: PuzzleArgs,
+
+    /// Read the puzzle JSON or an Across Lite .puz from a file, or `-` for
+    /// stdin, instead of downloading it
+    #[arg(short, long, conflicts_with_all = ["date", "offline", "refresh"])]
+    pub input: Option<PathBuf>,
+
+    /// Printer to send the ESC/POS stream to: a device or file path, a USB
+    /// printer as `usb:VID:PID`, a network printer as `tcp:HOST[:PORT]`, a
+    /// serial port as `serial:PATH`, `preview:FILE.png` or `preview:FILE.pdf`
+    /// to draw the receipt instead, or `-` for stdout (the default)
+    #[arg(short, long)]
+    pub output: Option<Target>,
+
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[command(flatten)]
+    pub layout: LayoutArgs,
+
+    /// What to do with the paper at the end [default: full]
+    #[arg(long, value_enum)]
+    pub cut: Option<Cut>,
+
+    /// Comma-separated code pages the printer supports, for printing
+    /// accented letters and punctuation [default: pc437]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub code_pages: Option<Vec<CodePage>>,
+
+    /// Draw characters that aren't in any of the code pages, like emoji,
+    /// instead of printing `?`
+    #[arg(long)]
+    pub raster_glyphs: bool,
+
+    /// Show what would be sent to the printer as a readable transcript,
+    /// instead of sending it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct ConnectionArgs {
+    /// Seconds to wait when connecting or writing to a network or serial
+    /// printer [default: 10]
+    #[arg(long)]
+    pub timeout: Option<u64>,
+
+    /// Baud rate for serial printers [default: 9600]
+    #[arg(long)]
+    pub baud_rate: Option<u32>,
+
+    /// Flow control for serial printers [default: none]
+    #[arg(long, value_enum)]
+    pub flow_control: Option<FlowControl>,
+}
+
+#[derive(Args)]
+pub struct PuzzleArgs {
+    /// Which crossword to print [default: mini]
+    #[arg(long = "puzzle", value_enum)]
+    pub variant: Option<Variant>,
+
+    /// Publication date of the puzzle (YYYY-MM-DD), defaults to today's
+    #[arg(short, long, value_parser = parse_date)]
+    pub date: Option<Date>,
+
+    /// Only use previously downloaded puzzles, never the network
+    #[arg(long, conflicts_with = "refresh")]
+    pub offline: bool,
+
+    /// Download the puzzle even if it's already cached
+    #[arg(long)]
+    pub refresh: bool,
+
+    /// How many times to retry a failed download [default: 3]
+    #[arg(long)]
+    pub retries: Option<u32>,
+
+    /// Seconds to keep trying to download the puzzle, retries included
+    /// [default: 60]
+    #[arg(long)]
+    pub download_timeout: Option<u64>,
+}
+
+#[derive(Args)]
+pub struct LayoutArgs {
+    /// Paper width: `58mm` (384 dots, the default), `80mm` (576 dots), or a
+    /// custom width in dots
+    #[arg(long)]
+    pub paper: Option<Paper>,
+
+    /// Width in dots of a single character in the printer's font [default: 12]
+    #[arg(long, value_parser = parse_font_width)]
+    pub font_width: Option<u8>,
+
+    /// Print resolution, used when rasterizing the grid [default: 203]
+    #[arg(long, value_parser = parse_dpi)]
+    pub dpi: Option<f32>,
+
+    /// Title printed at the top of the receipt [default: "The NYT Mini
+    /// Crossword", or "The NYT Crossword" for the daily]
+    #[arg(long)]
+    pub header: Option<String>,
+
+    /// Comma-separated parts of the receipt to print [default:
+    /// header,grid,clues,byline]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub sections: Option<Vec<Section>>,
+
+    /// How to draw the grid [default: native]
+    #[arg(long, value_enum)]
+    pub grid: Option<GridRenderer>,
+
+    /// Thickness in dots of the grid lines when drawing it natively [default: 2]
+    #[arg(long, value_parser = parse_line_width)]
+    pub line_width: Option<u32>,
+
+    /// Print the grid this many times larger, split into strips to tape
+    /// together side by side [default: 1]
+    #[arg(long, value_parser = parse_scale)]
+    pub grid_scale: Option<f32>,
+
+    /// Smallest size in dots for the squares of a natively drawn grid. Grids
+    /// that would need smaller squares are drawn bigger and split into strips
+    /// [default: 32]
+    #[arg(long, value_parser = parse_min_cell)]
+    pub min_cell: Option<u32>,
+
+    /// Print the clues with their numbers lined up in a narrow column, which
+    /// is the default for the daily
+    #[arg(long, overrides_with = "no_compact_clues")]
+    pub compact_clues: bool,
+
+    /// Print the clues under their numbers, even for the daily
+    #[arg(long, overrides_with = "compact_clues")]
+    pub no_compact_clues: bool,
+
+    /// Print the Across and Down clues side by side, which suits 80mm paper
+    #[arg(long)]
+    pub two_column_clues: bool,
+
+    /// How to print italics in the clues [default: underline]
+    #[arg(long, value_enum)]
+    pub italics: Option<Italics>,
+
+    /// Print the solution upside down, like the answers in a newspaper
+    #[arg(long)]
+    pub upside_down_solution: bool,
+
+    /// Print the grid and the clues side by side, turned to run along the
+    /// paper. Turn the receipt a quarter anticlockwise to read it
+    #[arg(long)]
+    pub landscape: bool,
+}
+
+impl LayoutArgs {
+    /// Whether `--compact-clues` or `--no-compact-clues` was given, whichever
+    /// came last.
+    pub fn compact_clues(&self) -> Option<bool> {
+        match (self.compact_clues, self.no_compact_clues) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct FetchArgs {
+    #[command(flatten)]
+    pub puzzle: PuzzleArgs,
+
+    /// File to write the JSON to, or `-` for stdout
+    #[arg(short, long, default_value = "-")]
+    pub output: PathBuf,
+}
+
+// The config file is checked with these too, so that it can't set anything
+// the flags wouldn't accept.
+
+/// Parse a number from `range`, where `what` is what it's for.
+fn parse_in<T>(s: &str, range: RangeInclusive<T>, what: &str) -> Result<T, String>
+where
+    T: FromStr + PartialOrd + Display,
+    T::Err: Display,
+{
+    let n = s.parse::<T>().map_err(|e| e.to_string())?;
+    if range.contains(&n) {
+        Ok(n)
+    } else {
+        Err(format!(
+            "{what} must be from {} to {}",
+            range.start(),
+            range.end()
+        ))
+    }
+}
+
+pub(crate) fn parse_font_width(s: &str) -> Result<u8, String> {
+    parse_in(s, 1..=u8::MAX, "the font width")
+}
+
+pub(crate) fn parse_dpi(s: &str) -> Result<f32, String> {
+    parse_in(s, 50.0..=1200.0, "the resolution")
+}
+
+pub(crate) fn parse_line_width(s: &str) -> Result<u32, String> {
+    parse_in(s, 1..=8, "the line width")
+}
+
+pub(crate) fn parse_min_cell(s: &str) -> Result<u32, String> {
+    parse_in(s, 8..=256, "the smallest square")
+}
+
+pub(crate) fn parse_scale(s: &str) -> Result<f32, String> {
+    parse_in(s, 1.0..=8.0, "the scale")
+}
+
+fn parse_date(s: &str) -> Result<Date, String> {
+    let date = s.parse::<Date>().map_err(|e| e.to_string())?;
+    // the next day's puzzle goes up the evening before in New York, which can
+    // already be tomorrow in UTC
+    let latest = Timestamp::now()
+        .to_zoned(TimeZone::UTC)
+        .date()
+        .tomorrow()
+        .map_err(|e| e.to_string())?;
+    // how far back depends on the puzzle, which isn't known yet
+    if date > latest {
+        Err(format!("{date} is in the future"))
+    } else {
+        Ok(date)
+    }
+}
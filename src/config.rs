@@ -0,0 +1,465 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::charset::CodePage;
+use crate::cli::{self, PrintArgs, PuzzleArgs};
+use crate::driver::Target;
+use crate::error::Error;
+use crate::fetch::PUZZLE_URL;
+use crate::paper::Paper;
+use crate::puzzle::{Crossword, Variant};
+
+/// The contents of `config.toml`. Everything is optional, and flags given on
+/// the command line take precedence.
+///
+/// ```toml
+/// [printer]
+/// output = "tcp:192.168.1.20"
+/// cut = "partial"
+/// code-pages = ["pc437", "wpc1252"]
+///
+/// [layout]
+/// paper = "80mm"
+/// sections = ["header", "grid", "clues"]
+///
+/// [download]
+/// puzzle = "daily"
+/// retries = 5
+/// url = "http://mirror.local/crosswords"
+/// nyt-s = "your subscriber cookie"
+/// headers = { "Accept-Language" = "en-US" }
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub printer: PrinterConfig,
+    pub layout: LayoutConfig,
+    pub download: DownloadConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PrinterConfig {
+    pub output: Option<Target>,
+    pub timeout: Option<u64>,
+    pub baud_rate: Option<u32>,
+    pub flow_control: Option<FlowControl>,
+    pub cut: Option<Cut>,
+    pub code_pages: Option<Vec<CodePage>>,
+    pub raster_glyphs: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LayoutConfig {
+    pub paper: Option<Paper>,
+    #[serde(deserialize_with = "font_width")]
+    pub font_width: Option<u8>,
+    #[serde(deserialize_with = "dpi")]
+    pub dpi: Option<f32>,
+    pub header: Option<String>,
+    pub sections: Option<Vec<Section>>,
+    pub upside_down_solution: Option<bool>,
+    pub grid: Option<GridRenderer>,
+    #[serde(deserialize_with = "line_width")]
+    pub line_width: Option<u32>,
+    #[serde(deserialize_with = "grid_scale")]
+    pub grid_scale: Option<f32>,
+    #[serde(deserialize_with = "min_cell")]
+    pub min_cell: Option<u32>,
+    pub compact_clues: Option<bool>,
+    pub italics: Option<Italics>,
+    pub two_column_clues: Option<bool>,
+    pub landscape: Option<bool>,
+}
+
+/// A number for one of the layout flags, checked the same way as on the
+/// command line.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn parse<'de, D, T>(
+        deserializer: D,
+        parse: fn(&str) -> Result<T, String>,
+    ) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let number = match Number::deserialize(deserializer)? {
+            Number::Int(n) => n.to_string(),
+            Number::Float(n) => n.to_string(),
+        };
+        parse(&number).map(Some).map_err(de::Error::custom)
+    }
+}
+
+fn font_width<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u8>, D::Error> {
+    Number::parse(deserializer, cli::parse_font_width)
+}
+
+fn dpi<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    Number::parse(deserializer, cli::parse_dpi)
+}
+
+fn line_width<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    Number::parse(deserializer, cli::parse_line_width)
+}
+
+fn grid_scale<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    Number::parse(deserializer, cli::parse_scale)
+}
+
+fn min_cell<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    Number::parse(deserializer, cli::parse_min_cell)
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DownloadConfig {
+    pub puzzle: Option<Variant>,
+    pub retries: Option<u32>,
+    pub timeout: Option<u64>,
+    pub url: Option<String>,
+    pub headers: BTreeMap<String, String>,
+    /// The value of the `NYT-S` cookie that logs in a subscriber.
+    pub nyt_s: Option<String>,
+}
+
+impl Config {
+    /// `~/.config/miniprint/config.toml` or the platform equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("miniprint").join("config.toml"))
+    }
+
+    /// Read the config file at `path`, or at the default path if none is
+    /// given. Only the default config file is allowed to not exist.
+    pub fn load(path: Option<&Path>) -> Result<Config, Error> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_owned(), true),
+            None => match Config::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !explicit => {
+                return Ok(Config::default())
+            }
+            Err(source) => return Err(Error::ReadConfig { path, source }),
+        };
+        toml::from_str(&text).map_err(|source| Error::ParseConfig { path, source })
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum FlowControl {
+    #[default]
+    None,
+    /// XON/XOFF
+    Software,
+    /// RTS/CTS
+    Hardware,
+}
+
+/// What to do with the paper once the receipt is printed.
+#[derive(Clone, Copy, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Cut {
+    #[default]
+    Full,
+    Partial,
+    /// Leave the paper attached, for printers without a cutter
+    None,
+}
+
+/// How to turn the puzzle into an image.
+#[derive(Clone, Copy, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum GridRenderer {
+    /// Draw the grid from its cells, falling back to the SVG if there are none
+    #[default]
+    Native,
+    /// Rasterize the SVG board that comes with the puzzle
+    Svg,
+}
+
+/// How to print italics, which receipt printers don't have.
+#[derive(Clone, Copy, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Italics {
+    #[default]
+    Underline,
+    /// Double-strike, which most printers print the same as bold
+    DoubleStrike,
+    /// Wrap the text in underscores, _like this_
+    Marker,
+}
+
+/// The parts of the receipt that can be turned on and off.
+#[derive(Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Section {
+    /// The title and date
+    Header,
+    Grid,
+    Clues,
+    /// The constructors and editor
+    Byline,
+    /// The answers, at the very bottom (not printed unless asked for)
+    Solution,
+}
+
+impl Section {
+    const DEFAULT: [Section; 4] = [
+        Section::Header,
+        Section::Grid,
+        Section::Clues,
+        Section::Byline,
+    ];
+}
+
+/// The config file and command line merged together, with defaults filled in.
+pub struct Settings {
+    pub download: Download,
+    pub output: Target,
+    pub connection: Connection,
+    pub layout: Layout,
+    pub cut: Cut,
+    /// The code pages the printer has, best first.
+    pub code_pages: Vec<CodePage>,
+    /// Draw characters the code pages don't have as user-defined characters.
+    pub raster_glyphs: bool,
+}
+
+/// Where to download the puzzle from, and how hard to try.
+pub struct Download {
+    pub variant: Variant,
+    /// Attempts after the first one fails.
+    pub retries: u32,
+    /// How long to keep trying for, across every attempt.
+    pub timeout: Duration,
+    /// The puzzle API, which serves `/mini.json` or `/daily.json` for the
+    /// current puzzle and `/mini/YYYY-MM-DD.json` or `/daily/YYYY-MM-DD.json`
+    /// for the others.
+    pub url: String,
+    /// Sent with every request.
+    pub headers: Vec<(String, String)>,
+}
+
+impl Download {
+    pub fn new(args: &PuzzleArgs, config: DownloadConfig) -> Download {
+        let default = Download::default();
+        let mut download = Download {
+            variant: args.variant.or(config.puzzle).unwrap_or_default(),
+            retries: args.retries.or(config.retries).unwrap_or(default.retries),
+            timeout: args
+                .download_timeout
+                .or(config.timeout)
+                .map_or(default.timeout, Duration::from_secs),
+            url: config
+                .url
+                .map_or(default.url, |url| url.trim_end_matches('/').to_owned()),
+            headers: default.headers,
+        };
+        for (name, value) in config.headers {
+            download.set_header(&name, value);
+        }
+        if let Some(nyt_s) = config.nyt_s {
+            let cookie = match download.header("Cookie") {
+                Some(cookie) => format!("{cookie}; NYT-S={nyt_s}"),
+                None => format!("NYT-S={nyt_s}"),
+            };
+            download.set_header("Cookie", cookie);
+        }
+        download
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let (_, value) = self
+            .headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))?;
+        Some(value)
+    }
+
+    /// Add a header, replacing any other with the same name.
+    pub fn set_header(&mut self, name: &str, value: String) {
+        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_owned(), value));
+    }
+}
+
+impl Default for Download {
+    fn default() -> Self {
+        Download {
+            variant: Variant::default(),
+            retries: 3,
+            timeout: Duration::from_secs(60),
+            url: PUZZLE_URL.to_owned(),
+            headers: vec![("User-Agent".to_owned(), "miniprinter".to_owned())],
+        }
+    }
+}
+
+/// How to talk to network and serial printers.
+pub struct Connection {
+    pub timeout: Duration,
+    pub baud_rate: u32,
+    pub flow_control: FlowControl,
+}
+
+/// How the receipt is laid out on the paper.
+#[derive(Clone)]
+pub struct Layout {
+    pub paper: Paper,
+    /// Width in dots of a single character in the printer's font.
+    pub font_width: u8,
+    pub dpi: f32,
+    /// The title at the top, or `None` for the puzzle's own
+    /// [heading](Crossword::heading).
+    pub header: Option<String>,
+    pub sections: Vec<Section>,
+    pub upside_down_solution: bool,
+    pub grid: GridRenderer,
+    /// Thickness in dots of the lines in a natively rendered grid.
+    pub line_width: u32,
+    /// How many times wider than the paper to print the grid, in strips.
+    pub grid_scale: f32,
+    /// Smallest size in dots of a natively rendered square. A grid that would
+    /// need smaller squares to fit is drawn bigger and tiled into strips.
+    pub min_cell: u32,
+    /// Line the clue numbers up in a narrow column, or `None` to do it only
+    /// for the daily.
+    pub compact_clues: Option<bool>,
+    pub italics: Italics,
+    /// Print the Across and Down clues next to each other, each in half the
+    /// width.
+    pub two_column_clues: bool,
+    /// Draw the header, grid, clues and byline side by side as one image,
+    /// turned to run along the paper.
+    pub landscape: bool,
+}
+
+impl Layout {
+    pub fn chars_per_line(&self) -> u8 {
+        self.paper.chars_per_line(self.font_width)
+    }
+
+    pub fn shows(&self, section: Section) -> bool {
+        self.sections.contains(&section)
+    }
+
+    /// The title to print at the top of `crossword`.
+    pub fn header<'a>(&'a self, crossword: &'a Crossword) -> &'a str {
+        self.header
+            .as_deref()
+            .unwrap_or_else(|| crossword.heading())
+    }
+
+    /// Whether to print the clues of `crossword` compactly.
+    pub fn compact_clues(&self, crossword: &Crossword) -> bool {
+        self.compact_clues
+            .unwrap_or(crossword.variant() == Variant::Daily)
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            paper: Paper::MM58,
+            font_width: 12,
+            dpi: 203.0,
+            header: None,
+            sections: Section::DEFAULT.to_vec(),
+            upside_down_solution: false,
+            grid: GridRenderer::default(),
+            line_width: 2,
+            grid_scale: 1.0,
+            min_cell: 32,
+            compact_clues: None,
+            italics: Italics::default(),
+            two_column_clues: false,
+            landscape: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn new(args: &PrintArgs, config: Config) -> Settings {
+        let Config {
+            printer,
+            layout,
+            download,
+        } = config;
+        let conn = &args.connection;
+        let lay = &args.layout;
+        let download = Download::new(&args.puzzle, download);
+        let default = Layout::default();
+        Settings {
+            output: args
+                .output
+                .clone()
+                .or(printer.output)
+                .unwrap_or(Target::Stdout),
+            connection: Connection {
+                timeout: Duration::from_secs(conn.timeout.or(printer.timeout).unwrap_or(10)),
+                baud_rate: conn.baud_rate.or(printer.baud_rate).unwrap_or(9600),
+                flow_control: conn
+                    .flow_control
+                    .or(printer.flow_control)
+                    .unwrap_or_default(),
+            },
+            layout: Layout {
+                paper: lay.paper.or(layout.paper).unwrap_or(default.paper),
+                font_width: lay
+                    .font_width
+                    .or(layout.font_width)
+                    .unwrap_or(default.font_width),
+                dpi: lay.dpi.or(layout.dpi).unwrap_or(default.dpi),
+                header: lay.header.clone().or(layout.header),
+                sections: lay
+                    .sections
+                    .clone()
+                    .or(layout.sections)
+                    .unwrap_or(default.sections),
+                upside_down_solution: lay.upside_down_solution
+                    || layout.upside_down_solution.unwrap_or(false),
+                grid: lay.grid.or(layout.grid).unwrap_or_default(),
+                line_width: lay
+                    .line_width
+                    .or(layout.line_width)
+                    .unwrap_or(default.line_width),
+                grid_scale: lay
+                    .grid_scale
+                    .or(layout.grid_scale)
+                    .unwrap_or(default.grid_scale),
+                min_cell: lay.min_cell.or(layout.min_cell).unwrap_or(default.min_cell),
+                compact_clues: lay.compact_clues().or(layout.compact_clues),
+                italics: lay.italics.or(layout.italics).unwrap_or_default(),
+                two_column_clues: lay.two_column_clues || layout.two_column_clues.unwrap_or(false),
+                landscape: lay.landscape || layout.landscape.unwrap_or(false),
+            },
+            cut: args.cut.or(printer.cut).unwrap_or_default(),
+            code_pages: args
+                .code_pages
+                .clone()
+                .or(printer.code_pages)
+                .unwrap_or_else(|| vec![CodePage::default()]),
+            raster_glyphs: args.raster_glyphs || printer.raster_glyphs.unwrap_or(false),
+            download,
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! Reads back the ESC/POS that we send, for previewing a receipt or checking
+//! what would be printed without a printer. Only the commands miniprint
+//! itself uses are understood; anything else comes out as
+//! [`Command::Unknown`].
+
+use std::fmt::Write as _;
+
+use crate::charset::CodePage;
+
+const LF: u8 = 0x0a;
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+const CAN: u8 = 0x18;
+
+#[derive(Debug, PartialEq)]
+pub enum Command<'a> {
+    /// Characters to print, in the current code page.
+    Text(&'a [u8]),
+    /// Print the line so far and feed this many lines.
+    Feed(u8),
+    Init,
+    Cancel,
+    Bold(bool),
+    /// 0 for none, 1 for single and 2 for double
+    Underline(u8),
+    DoubleStrike(bool),
+    UpsideDown(bool),
+    /// 0 for left, 1 for center and 2 for right
+    Justify(u8),
+    /// The code page number, as sent by ESC t
+    PageCode(u8),
+    DefineCharacters(Vec<UserCharacter<'a>>),
+    UserCharacters(bool),
+    /// A GS v 0 raster image, a row at a time with the leftmost dot in the
+    /// high bit of each byte.
+    Image {
+        width_bytes: u16,
+        height: u16,
+        data: &'a [u8],
+    },
+    Cut {
+        partial: bool,
+    },
+    Unknown(&'a [u8]),
+}
+
+/// A character defined with ESC &: a column at a time from the left, each
+/// column `height` bytes from the top.
+#[derive(Debug, PartialEq)]
+pub struct UserCharacter<'a> {
+    pub code: u8,
+    pub height: u8,
+    pub width: u8,
+    pub data: &'a [u8],
+}
+
+pub fn decode(mut data: &[u8]) -> Vec<Command<'_>> {
+    let mut commands = Vec::new();
+    while !data.is_empty() {
+        let (command, len) = next(data).unwrap_or((Command::Unknown(data), data.len()));
+        commands.push(command);
+        data = &data[len..];
+    }
+    commands
+}
+
+/// The command at the start of `data` and its length, or `None` if it's cut
+/// off.
+fn next(data: &[u8]) -> Option<(Command<'_>, usize)> {
+    let arg = |i: usize| data.get(i).copied();
+    let flag = |i: usize| Some(arg(i)? & 1 == 1);
+    let command = match (data[0], arg(1)) {
+        (LF, _) => (Command::Feed(1), 1),
+        (CAN, _) => (Command::Cancel, 1),
+        (ESC, Some(b'@')) => (Command::Init, 2),
+        (ESC, Some(b'd')) => (Command::Feed(arg(2)?), 3),
+        (ESC, Some(b'E')) => (Command::Bold(flag(2)?), 3),
+        (ESC, Some(b'-')) => (Command::Underline(arg(2)? % b'0'), 3),
+        (ESC, Some(b'G')) => (Command::DoubleStrike(flag(2)?), 3),
+        (ESC, Some(b'{')) => (Command::UpsideDown(flag(2)?), 3),
+        (ESC, Some(b'a')) => (Command::Justify(arg(2)? % b'0'), 3),
+        (ESC, Some(b't')) => (Command::PageCode(arg(2)?), 3),
+        (ESC, Some(b'%')) => (Command::UserCharacters(flag(2)?), 3),
+        (ESC, Some(b'&')) => {
+            let (height, first, last) = (arg(2)?, arg(3)?, arg(4)?);
+            let mut chars = Vec::new();
+            let mut i = 5;
+            for code in first..=last {
+                let width = arg(i)?;
+                let len = usize::from(height) * usize::from(width);
+                let data = data.get(i + 1..i + 1 + len)?;
+                chars.push(UserCharacter {
+                    code,
+                    height,
+                    width,
+                    data,
+                });
+                i += 1 + len;
+            }
+            (Command::DefineCharacters(chars), i)
+        }
+        (GS, Some(b'v')) if arg(2)? == b'0' => {
+            let width_bytes = u16::from_le_bytes([arg(4)?, arg(5)?]);
+            let height = u16::from_le_bytes([arg(6)?, arg(7)?]);
+            let len = usize::from(width_bytes) * usize::from(height);
+            let data = data.get(8..8 + len)?;
+            let image = Command::Image {
+                width_bytes,
+                height,
+                data,
+            };
+            (image, 8 + len)
+        }
+        // function B feeds the paper up to the cutter first, by n more dots
+        (GS, Some(b'V')) => match arg(2)? {
+            m @ (b'A' | b'B') => {
+                arg(3)?;
+                (Command::Cut { partial: m == b'B' }, 4)
+            }
+            m => (
+                Command::Cut {
+                    partial: m & 1 == 1,
+                },
+                3,
+            ),
+        },
+        (ESC | GS, _) => (Command::Unknown(data.get(..2)?), 2),
+        (b, _) if b < 0x20 => (Command::Unknown(&data[..1]), 1),
+        _ => {
+            let len = data.iter().position(|&b| b < 0x20).unwrap_or(data.len());
+            (Command::Text(&data[..len]), len)
+        }
+    };
+    Some(command)
+}
+
+/// Turn an ESC/POS stream into text that can be read and diffed: one line per
+/// line feed, with the printed text as-is and every other command in
+/// brackets where it happens.
+///
+/// ```text
+/// 4: [bold]Cornell[/bold] or [underline]Harvard[/underline], e.g.
+/// [cancel][image 384x392 #1f0e93c2]
+/// ```
+pub fn transcript(data: &[u8]) -> String {
+    let mut out = String::new();
+    let mut page = CodePage::default();
+    let on_off = |on: bool| if on { "" } else { "/" };
+    for command in decode(data) {
+        match command {
+            Command::Text(text) => {
+                for &b in text {
+                    match page.char(b) {
+                        Some(c) => out.push(c),
+                        None => {
+                            let _ = write!(out, "[?{b:02x}]");
+                        }
+                    }
+                }
+            }
+            Command::Feed(lines) => {
+                for _ in 0..lines {
+                    out.push('\n');
+                }
+            }
+            Command::Init => out.push_str("[init]"),
+            Command::Cancel => out.push_str("[cancel]"),
+            Command::Bold(on) => {
+                let _ = write!(out, "[{}bold]", on_off(on));
+            }
+            Command::Underline(0) => out.push_str("[/underline]"),
+            Command::Underline(1) => out.push_str("[underline]"),
+            Command::Underline(mode) => {
+                let _ = write!(out, "[underline {mode}]");
+            }
+            Command::DoubleStrike(on) => {
+                let _ = write!(out, "[{}double-strike]", on_off(on));
+            }
+            Command::UpsideDown(on) => {
+                let _ = write!(out, "[{}upside-down]", on_off(on));
+            }
+            Command::Justify(justify) => out.push_str(match justify {
+                0 => "[left]",
+                1 => "[center]",
+                _ => "[right]",
+            }),
+            Command::PageCode(n) => {
+                page = CodePage::from_number(n).unwrap_or(page);
+                let _ = write!(out, "[code page {n}]");
+            }
+            Command::DefineCharacters(chars) => {
+                for c in chars {
+                    let _ = write!(
+                        out,
+                        "[define {:?} {}x{} #{:08x}]",
+                        char::from(c.code),
+                        c.width,
+                        u32::from(c.height) * 8,
+                        hash(c.data)
+                    );
+                }
+            }
+            Command::UserCharacters(on) => {
+                let _ = write!(out, "[{}user characters]", on_off(on));
+            }
+            Command::Image {
+                width_bytes,
+                height,
+                data,
+            } => {
+                let width = u32::from(width_bytes) * 8;
+                let _ = write!(out, "[image {width}x{height} #{:08x}]", hash(data));
+            }
+            Command::Cut { partial: false } => out.push_str("[cut]"),
+            Command::Cut { partial: true } => out.push_str("[partial cut]"),
+            Command::Unknown(bytes) => {
+                out.push_str("[unknown");
+                for b in bytes {
+                    let _ = write!(out, " {b:02x}");
+                }
+                out.push(']');
+            }
+        }
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// FNV-1a, so that images show up in a transcript as something short that
+/// still changes whenever they do.
+fn hash(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c9dc5, |hash, &b| {
+        (hash ^ u32::from(b)).wrapping_mul(0x01000193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_commands() {
+        let data = b"\x1b\x74\x10Hi\x93\x1bd\x02\x1b-\x01x\x1dv0\x00\x01\x00\x02\x00\xf0\x0f\x1dVA\x00\x1bZ";
+        assert_eq!(
+            decode(data),
+            [
+                Command::PageCode(16),
+                Command::Text(b"Hi\x93"),
+                Command::Feed(2),
+                Command::Underline(1),
+                Command::Text(b"x"),
+                Command::Image {
+                    width_bytes: 1,
+                    height: 2,
+                    data: b"\xf0\x0f"
+                },
+                Command::Cut { partial: false },
+                Command::Unknown(b"\x1bZ"),
+            ]
+        );
+        // cut off halfway through an image
+        assert_eq!(
+            decode(b"\x1dv0\x00\x01\x00\x02\x00\xf0"),
+            [Command::Unknown(b"\x1dv0\x00\x01\x00\x02\x00\xf0")]
+        );
+    }
+
+    #[test]
+    fn transcribes_lines_and_styles() {
+        let data = b"Across:\x1bd\x014: \x1bE\x01Cornell\x1bE\x00 \x1b-\x01na\x8cve\x1b-\x00\x0a\x18\x1dVA\x00";
+        assert_eq!(
+            transcript(data),
+            "Across:\n4: [bold]Cornell[/bold] [underline]na\u{ee}ve[/underline]\n[cancel][cut]\n"
+        );
+    }
+}
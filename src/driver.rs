@@ -0,0 +1,326 @@
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[cfg(feature = "usb")]
+use escpos::driver::NativeUsbDriver;
+use escpos::driver::{ConsoleDriver, Driver, FileDriver};
+use escpos::errors;
+
+use serde::Deserialize;
+
+#[cfg(feature = "serial")]
+use crate::config::FlowControl;
+use crate::config::Settings;
+use crate::preview::PreviewDriver;
+
+/// Where to send the ESC/POS stream, as given on the command line.
+///
+/// - `-` is stdout
+/// - `usb:VID:PID` is a USB printer, with the IDs in hex (e.g. `usb:0416:5011`)
+/// - `tcp:HOST[:PORT]` is a network printer, on port 9100 unless specified
+/// - `serial:PATH` is a serial port, like `/dev/ttyUSB0` or `COM3`
+/// - `preview:PATH` draws the receipt to a PNG, or a PDF if PATH ends in `.pdf`
+/// - anything else is a path to a file or device node, like `/dev/usb/lp0`
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Target {
+    Stdout,
+    File(PathBuf),
+    Usb { vendor_id: u16, product_id: u16 },
+    Network { host: String, port: u16 },
+    Serial(String),
+    Preview(PathBuf),
+}
+
+/// The raw printing port used by most network receipt printers.
+const DEFAULT_PORT: u16 = 9100;
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "-" {
+            return Ok(Target::Stdout);
+        }
+        if let Some(ids) = s.strip_prefix("usb:") {
+            let parse_id = |id: &str| {
+                u16::from_str_radix(id, 16).map_err(|e| format!("invalid USB id {id:?}: {e}"))
+            };
+            let (vendor_id, product_id) = ids
+                .split_once(':')
+                .ok_or("expected a USB device as usb:VID:PID")?;
+            return Ok(Target::Usb {
+                vendor_id: parse_id(vendor_id)?,
+                product_id: parse_id(product_id)?,
+            });
+        }
+        if let Some(addr) = s.strip_prefix("tcp:") {
+            let addr = addr.strip_prefix("//").unwrap_or(addr);
+            // only look for a port after the closing bracket of an IPv6 address
+            let port_start = addr.rfind(']').unwrap_or(0);
+            let (host, port) = match addr[port_start..].rfind(':') {
+                Some(i) => {
+                    let (host, port) = addr.split_at(port_start + i);
+                    let port = port[1..]
+                        .parse()
+                        .map_err(|e| format!("invalid port {:?}: {e}", &port[1..]))?;
+                    (host, port)
+                }
+                None => (addr, DEFAULT_PORT),
+            };
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            if host.is_empty() {
+                return Err("expected a network printer as tcp:HOST[:PORT]".to_owned());
+            }
+            return Ok(Target::Network {
+                host: host.to_owned(),
+                port,
+            });
+        }
+        if let Some(path) = s.strip_prefix("serial:") {
+            return Ok(Target::Serial(path.to_owned()));
+        }
+        if let Some(path) = s.strip_prefix("preview:") {
+            return Ok(Target::Preview(path.into()));
+        }
+        Ok(Target::File(s.into()))
+    }
+}
+
+impl TryFrom<String> for Target {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// The printer backends selectable at runtime.
+pub enum Output {
+    Console(ConsoleDriver),
+    File(FileDriver),
+    #[cfg(feature = "usb")]
+    Usb(NativeUsbDriver),
+    Network(NetworkDriver),
+    #[cfg(feature = "serial")]
+    Serial(SerialDriver),
+    Preview(PreviewDriver),
+    Memory(MemoryDriver),
+}
+
+impl Output {
+    pub fn open(settings: &Settings) -> errors::Result<Self> {
+        let conn = &settings.connection;
+        let timeout = conn.timeout;
+        match settings.output {
+            Target::Stdout => Ok(Output::Console(ConsoleDriver::open(true))),
+            Target::File(ref path) => FileDriver::open(path).map(Output::File),
+            #[cfg(feature = "usb")]
+            Target::Usb {
+                vendor_id,
+                product_id,
+            } => NativeUsbDriver::open(vendor_id, product_id).map(Output::Usb),
+            #[cfg(not(feature = "usb"))]
+            Target::Usb {
+                vendor_id,
+                product_id,
+            } => Err(errors::PrinterError::Io(format!(
+                "can't open USB printer {vendor_id:04x}:{product_id:04x}: \
+                 miniprint was built without USB support"
+            ))),
+            Target::Network { ref host, port } => {
+                NetworkDriver::open(host, port, timeout).map(Output::Network)
+            }
+            #[cfg(feature = "serial")]
+            Target::Serial(ref path) => {
+                SerialDriver::open(path, conn.baud_rate, conn.flow_control, timeout)
+                    .map(Output::Serial)
+            }
+            #[cfg(not(feature = "serial"))]
+            Target::Serial(ref path) => Err(errors::PrinterError::Io(format!(
+                "can't open serial port {path}: miniprint was built without serial support"
+            ))),
+            Target::Preview(ref path) => {
+                Ok(Output::Preview(PreviewDriver::open(path, &settings.layout)))
+            }
+        }
+    }
+
+    fn driver(&self) -> &dyn Driver {
+        match self {
+            Output::Console(d) => d,
+            Output::File(d) => d,
+            #[cfg(feature = "usb")]
+            Output::Usb(d) => d,
+            Output::Network(d) => d,
+            #[cfg(feature = "serial")]
+            Output::Serial(d) => d,
+            Output::Preview(d) => d,
+            Output::Memory(d) => d,
+        }
+    }
+}
+
+impl Driver for Output {
+    fn name(&self) -> String {
+        self.driver().name()
+    }
+
+    fn write(&self, data: &[u8]) -> errors::Result<()> {
+        self.driver().write(data)
+    }
+
+    fn read(&self, buf: &mut [u8]) -> errors::Result<usize> {
+        self.driver().read(buf)
+    }
+
+    fn flush(&self) -> errors::Result<()> {
+        self.driver().flush()
+    }
+}
+
+/// Keeps everything written to it, for dry runs. Clones share the same buffer,
+/// so one can be handed to the `Printer` and the other used to read it back.
+#[derive(Clone, Default)]
+pub struct MemoryDriver {
+    data: Rc<RefCell<Vec<u8>>>,
+}
+
+impl MemoryDriver {
+    pub fn data(&self) -> Vec<u8> {
+        self.data.borrow().clone()
+    }
+}
+
+impl Driver for MemoryDriver {
+    fn name(&self) -> String {
+        "memory".to_owned()
+    }
+
+    fn write(&self, data: &[u8]) -> errors::Result<()> {
+        self.data.try_borrow_mut()?.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> errors::Result<usize> {
+        Ok(0)
+    }
+
+    fn flush(&self) -> errors::Result<()> {
+        Ok(())
+    }
+}
+
+/// A printer listening for raw ESC/POS on a TCP port.
+///
+/// Unlike `escpos::driver::NetworkDriver`, this resolves hostnames and
+/// applies the timeout to every address it tries.
+pub struct NetworkDriver {
+    addr: String,
+    stream: RefCell<TcpStream>,
+}
+
+impl NetworkDriver {
+    pub fn open(host: &str, port: u16, timeout: Duration) -> errors::Result<Self> {
+        let addr = if host.contains(':') {
+            format!("[{host}]:{port}")
+        } else {
+            format!("{host}:{port}")
+        };
+        let unreachable =
+            |e| errors::PrinterError::Io(format!("couldn't connect to printer at {addr}: {e}"));
+
+        let mut last_err = None;
+        for sock_addr in addr.to_socket_addrs().map_err(unreachable)? {
+            match TcpStream::connect_timeout(&sock_addr, timeout) {
+                Ok(stream) => {
+                    stream.set_write_timeout(Some(timeout))?;
+                    stream.set_read_timeout(Some(timeout))?;
+                    return Ok(NetworkDriver {
+                        addr,
+                        stream: RefCell::new(stream),
+                    });
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        let e = last_err.unwrap_or_else(|| std::io::ErrorKind::NotFound.into());
+        Err(unreachable(e))
+    }
+}
+
+impl Driver for NetworkDriver {
+    fn name(&self) -> String {
+        format!("network ({})", self.addr)
+    }
+
+    fn write(&self, data: &[u8]) -> errors::Result<()> {
+        Ok(self.stream.try_borrow_mut()?.write_all(data)?)
+    }
+
+    fn read(&self, buf: &mut [u8]) -> errors::Result<usize> {
+        Ok(self.stream.try_borrow_mut()?.read(buf)?)
+    }
+
+    fn flush(&self) -> errors::Result<()> {
+        Ok(self.stream.try_borrow_mut()?.flush()?)
+    }
+}
+
+/// A printer on a serial port, usually RS-232 or a USB-serial adapter.
+#[cfg(feature = "serial")]
+pub struct SerialDriver {
+    path: String,
+    port: RefCell<Box<dyn serialport::SerialPort>>,
+}
+
+#[cfg(feature = "serial")]
+impl SerialDriver {
+    pub fn open(
+        path: &str,
+        baud_rate: u32,
+        flow_control: FlowControl,
+        timeout: Duration,
+    ) -> errors::Result<Self> {
+        let flow_control = match flow_control {
+            FlowControl::None => serialport::FlowControl::None,
+            FlowControl::Software => serialport::FlowControl::Software,
+            FlowControl::Hardware => serialport::FlowControl::Hardware,
+        };
+        let port = serialport::new(path, baud_rate)
+            .flow_control(flow_control)
+            .timeout(timeout)
+            .open()
+            .map_err(|e| {
+                errors::PrinterError::Io(format!("couldn't open serial port {path}: {e}"))
+            })?;
+        Ok(SerialDriver {
+            path: path.to_owned(),
+            port: RefCell::new(port),
+        })
+    }
+}
+
+#[cfg(feature = "serial")]
+impl Driver for SerialDriver {
+    fn name(&self) -> String {
+        format!("serial ({})", self.path)
+    }
+
+    fn write(&self, data: &[u8]) -> errors::Result<()> {
+        Ok(self.port.try_borrow_mut()?.write_all(data)?)
+    }
+
+    fn read(&self, buf: &mut [u8]) -> errors::Result<usize> {
+        Ok(self.port.try_borrow_mut()?.read(buf)?)
+    }
+
+    fn flush(&self) -> errors::Result<()> {
+        Ok(self.port.try_borrow_mut()?.flush()?)
+    }
+}
@@ -0,0 +1,131 @@
+use std::io;
+use std::path::PathBuf;
+
+use escpos::errors::PrinterError;
+use jiff::civil::Date;
+use resvg::usvg;
+use thiserror::Error;
+
+/// Everything that can stop a puzzle from being printed.
+///
+/// Each kind of failure exits with its own status (see [`Error::exit_code`]),
+/// so that a script running miniprint can tell a flaky network, which is
+/// worth retrying, from a problem that will happen again.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("couldn't read {}: {source}", path.display())]
+    ReadConfig { path: PathBuf, source: io::Error },
+
+    #[error("invalid config {}: {source}", path.display())]
+    ParseConfig {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("invalid proxy in {var}: {message}")]
+    Proxy { var: &'static str, message: String },
+
+    /// The NYT couldn't be reached, or the connection broke.
+    #[error("couldn't download {url}: {source}")]
+    Network {
+        url: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// The NYT answered, but not with a puzzle.
+    #[error("couldn't download {url}: HTTP {status}{}", status_hint(*status))]
+    Status { url: String, status: u16 },
+
+    #[error("{puzzle} was first published on {first}")]
+    TooEarly { puzzle: &'static str, first: Date },
+
+    #[error("no cached puzzle for {0}, and --offline won't download it")]
+    NotCached(Date),
+
+    /// The JSON isn't a Mini, or the NYT has changed its format.
+    #[error("the puzzle isn't in the format miniprint expects: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("couldn't read the .puz file: {0}")]
+    Puz(String),
+
+    #[error("couldn't read the puzzle's SVG board: {0}")]
+    Svg(#[from] usvg::Error),
+
+    #[error("the grid would be {width}x{height} dots, which is too small to draw")]
+    EmptyCanvas { width: f32, height: f32 },
+
+    #[error("couldn't encode the grid as an image: {0}")]
+    Image(String),
+
+    #[error("printer error: {0}")]
+    Printer(#[from] PrinterError),
+
+    #[error("{}: {source}", path.display())]
+    File { path: PathBuf, source: io::Error },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// What each exit status means, for the end of `--help`. Keep it in step
+/// with [`Error::exit_code`].
+pub(crate) const EXIT_STATUS: &str = "\
+Exit status:
+  1   couldn't read or write a file
+  2   bad arguments, or a date before the puzzle was first published
+  3   bad config file or proxy variable
+  4   couldn't reach the NYT, or it's busy (worth retrying)
+  5   the NYT refused the download, or there's no such puzzle
+  6   --offline and the puzzle isn't cached
+  7   the puzzle JSON isn't in the expected format, or the .puz is broken
+  8   the puzzle's SVG board doesn't parse
+  9   the grid image couldn't be made
+  10  printer error";
+
+impl Error {
+    /// The process exit status for this error, as listed at the end of
+    /// `miniprint --help`. Bad arguments exit with 2, like the ones clap
+    /// reports itself.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::File { .. } | Error::Io(_) => 1,
+            Error::TooEarly { .. } => 2,
+            Error::ReadConfig { .. } | Error::ParseConfig { .. } | Error::Proxy { .. } => 3,
+            Error::Network { .. } => 4,
+            // the NYT being busy is worth retrying too
+            Error::Status { .. } if self.is_retryable() => 4,
+            Error::Status { .. } => 5,
+            Error::NotCached(_) => 6,
+            Error::Json(_) | Error::Puz(_) => 7,
+            Error::Svg(_) => 8,
+            Error::EmptyCanvas { .. } | Error::Image(_) => 9,
+            Error::Printer(_) => 10,
+        }
+    }
+
+    /// Whether trying the download again later might work: the NYT couldn't
+    /// be reached, or it's busy or having trouble.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Network { .. } => true,
+            Error::Status { status, .. } => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+/// What to do about an HTTP error from the NYT.
+fn status_hint(status: u16) -> &'static str {
+    match status {
+        401 | 403 => {
+            " (the NYT refused the download; the daily needs a subscriber's \
+             cookie as nyt-s under [download] in the config, or save the JSON \
+             from a browser and print it with --input instead)"
+        }
+        404 => " (there's no puzzle for that date, or it isn't out yet)",
+        429 => " (too many requests; try again later)",
+        500.. => " (the NYT is having trouble; try again later)",
+        _ => "",
+    }
+}
@@ -0,0 +1,316 @@
+//! Downloading puzzles from the NYT.
+
+use std::env;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use jiff::civil::Date;
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+
+use crate::config::Download;
+use crate::error::Error;
+
+pub const PUZZLE_URL: &str = "https://www.nytimes.com/svc/crosswords/v6/puzzle";
+
+/// How long to wait before the first retry. Each one after that waits twice
+/// as long as the last.
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The date of the current puzzle, as far as the NYT is concerned.
+pub fn today() -> Date {
+    let tz = TimeZone::get("America/New_York").unwrap_or(TimeZone::UTC);
+    Timestamp::now().to_zoned(tz).date()
+}
+
+/// Download the JSON for the crossword published on `date`, or for the
+/// current one if there's no date. Parse it with `serde_json` into a
+/// [`Crossword`](crate::Crossword).
+///
+/// Network errors and server errors are retried as `download` says, backing
+/// off exponentially, and `on_retry` is told about each one along with how
+/// long until the next try. Requests go through the proxy in `HTTPS_PROXY`,
+/// `HTTP_PROXY` or `ALL_PROXY` if there is one.
+pub fn download(
+    date: Option<Date>,
+    download: &Download,
+    on_retry: impl FnMut(&Error, Duration),
+) -> Result<String, Error> {
+    let url = match date {
+        Some(date) => format!("{}/{}/{date}.json", download.url, download.variant.name()),
+        None => format!("{}/{}.json", download.url, download.variant.name()),
+    };
+    get_with_retries(&url, download, FIRST_BACKOFF, on_retry)
+}
+
+fn get_with_retries(
+    url: &str,
+    download: &Download,
+    backoff: Duration,
+    mut on_retry: impl FnMut(&Error, Duration),
+) -> Result<String, Error> {
+    let mut agent = ureq::AgentBuilder::new();
+    if let Some((var, proxy)) = proxy_from_env(url, |var| env::var(var).ok()) {
+        let proxy = ureq::Proxy::new(proxy).map_err(|e| Error::Proxy {
+            var,
+            message: e.to_string(),
+        })?;
+        agent = agent.proxy(proxy);
+    }
+    let agent = agent.build();
+
+    let deadline = Instant::now() + download.timeout;
+    let mut backoff = backoff;
+    let mut attempt = 0;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let e = match get(&agent, url, &download.headers, remaining) {
+            Ok(json) => return Ok(json),
+            Err(e) => e,
+        };
+        attempt += 1;
+        // the attempt itself may have used up most of what was left
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if !e.is_retryable() || attempt > download.retries || backoff >= remaining {
+            return Err(e);
+        }
+        on_retry(&e, backoff);
+        thread::sleep(backoff);
+        backoff *= 2;
+    }
+}
+
+fn get(
+    agent: &ureq::Agent,
+    url: &str,
+    headers: &[(String, String)],
+    timeout: Duration,
+) -> Result<String, Error> {
+    let mut request = agent.get(url).timeout(timeout);
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    let response = match request.call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(status, _)) => {
+            return Err(Error::Status {
+                url: url.to_owned(),
+                status,
+            })
+        }
+        Err(ureq::Error::Transport(e)) => {
+            return Err(Error::Network {
+                url: url.to_owned(),
+                source: e.into(),
+            })
+        }
+    };
+    response.into_string().map_err(|e| Error::Network {
+        url: url.to_owned(),
+        source: e.into(),
+    })
+}
+
+/// The proxy for `url` from the usual environment variables, if it isn't one
+/// of the hosts in `NO_PROXY`, along with the variable it came from. `env`
+/// looks up a variable.
+fn proxy_from_env(
+    url: &str,
+    env: impl Fn(&str) -> Option<String>,
+) -> Option<(&'static str, String)> {
+    let var = |name: &'static str| {
+        let lower: &'static str = match name {
+            "HTTPS_PROXY" => "https_proxy",
+            "HTTP_PROXY" => "http_proxy",
+            "ALL_PROXY" => "all_proxy",
+            _ => "no_proxy",
+        };
+        [lower, name]
+            .into_iter()
+            .find_map(|name| Some((name, env(name).filter(|v| !v.is_empty())?)))
+    };
+
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split('/').next()?;
+    let authority = authority.rsplit('@').next()?;
+    let host = match authority.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next()?,
+        None => authority.split(':').next()?,
+    };
+    if let Some((_, no_proxy)) = var("NO_PROXY") {
+        let skip = no_proxy.split(',').map(str::trim).any(|pattern| {
+            let pattern = pattern.trim_start_matches('.');
+            pattern == "*"
+                || host.eq_ignore_ascii_case(pattern)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", pattern.to_ascii_lowercase()))
+        });
+        if skip {
+            return None;
+        }
+    }
+    let scheme_var = if scheme.eq_ignore_ascii_case("https") {
+        "HTTPS_PROXY"
+    } else {
+        "HTTP_PROXY"
+    };
+    var(scheme_var).or_else(|| var("ALL_PROXY"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+
+    /// Serve one response per connection from `responses`, as
+    /// `(status, body)` or 0 to hang up, and return the URL to fetch.
+    fn stub_server(responses: Vec<(u16, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/mini.json", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                if status == 0 {
+                    continue;
+                }
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let response = format!(
+                    "HTTP/1.1 {status} Whatever\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        });
+        url
+    }
+
+    fn download(retries: u32) -> Download {
+        Download {
+            retries,
+            timeout: Duration::from_secs(10),
+            ..Download::default()
+        }
+    }
+
+    #[test]
+    fn retries_server_errors() {
+        let url = stub_server(vec![(503, ""), (502, ""), (200, "{}")]);
+        let mut retried = Vec::new();
+        let json = get_with_retries(
+            &url,
+            &download(2),
+            Duration::from_millis(1),
+            |e, backoff| {
+                retried.push((e.to_string(), backoff));
+            },
+        )
+        .unwrap();
+        assert_eq!(json, "{}");
+        // each one backs off twice as long as the last
+        assert_eq!(retried.len(), 2);
+        assert!(retried[0].0.contains("HTTP 503"), "{}", retried[0].0);
+        assert_eq!(retried[1].1, Duration::from_millis(2));
+    }
+
+    #[test]
+    fn gives_up_after_retries() {
+        let url = stub_server(vec![(503, ""), (503, ""), (200, "{}")]);
+        let e =
+            get_with_retries(&url, &download(1), Duration::from_millis(1), |_, _| {}).unwrap_err();
+        assert!(matches!(e, Error::Status { status: 503, .. }), "{e}");
+    }
+
+    #[test]
+    fn doesnt_retry_client_errors() {
+        let url = stub_server(vec![(404, ""), (200, "{}")]);
+        let e =
+            get_with_retries(&url, &download(3), Duration::from_millis(1), |_, _| {}).unwrap_err();
+        assert!(matches!(e, Error::Status { status: 404, .. }), "{e}");
+    }
+
+    #[test]
+    fn gives_up_at_the_timeout() {
+        let url = stub_server(vec![(503, ""), (200, "{}")]);
+        let download = Download {
+            retries: 3,
+            timeout: Duration::from_millis(50),
+            ..Download::default()
+        };
+        let e = get_with_retries(&url, &download, Duration::from_secs(1), |_, _| {}).unwrap_err();
+        assert!(matches!(e, Error::Status { status: 503, .. }), "{e}");
+    }
+
+    #[test]
+    fn counts_slow_failures_against_the_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/mini.json", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                thread::sleep(Duration::from_millis(300));
+                let response =
+                    "HTTP/1.1 503 Busy\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+                let _ = stream.unwrap().write_all(response.as_bytes());
+            }
+        });
+        let download = Download {
+            retries: 3,
+            timeout: Duration::from_millis(400),
+            ..Download::default()
+        };
+        let start = Instant::now();
+        let e =
+            get_with_retries(&url, &download, Duration::from_millis(200), |_, _| {}).unwrap_err();
+        assert!(matches!(e, Error::Status { status: 503, .. }), "{e}");
+        // no sleeping and trying again with only 100ms left
+        assert!(
+            start.elapsed() < Duration::from_millis(450),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    #[test]
+    fn retries_dropped_connections() {
+        let url = stub_server(vec![(0, ""), (200, "{}")]);
+        let json =
+            get_with_retries(&url, &download(1), Duration::from_millis(1), |_, _| {}).unwrap();
+        assert_eq!(json, "{}");
+    }
+
+    #[test]
+    fn picks_proxy_by_scheme() {
+        let vars = [
+            ("https_proxy", "http://secure:3128"),
+            ("HTTP_PROXY", "http://plain:3128"),
+            ("NO_PROXY", "localhost, .internal"),
+        ];
+        let env = |name: &str| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        };
+        let proxy = |url| proxy_from_env(url, env);
+
+        assert_eq!(
+            proxy("https://www.nytimes.com/svc"),
+            Some(("https_proxy", "http://secure:3128".to_owned()))
+        );
+        assert_eq!(
+            proxy("http://user@mirror:8080/svc"),
+            Some(("HTTP_PROXY", "http://plain:3128".to_owned()))
+        );
+        assert_eq!(proxy("http://localhost:8080/svc"), None);
+        assert_eq!(proxy("http://mirror.internal/svc"), None);
+        assert_eq!(
+            proxy("http://internal.example/svc").unwrap().0,
+            "HTTP_PROXY"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! The system fonts used to draw text ourselves.
+
+use std::fmt::Write as _;
+
+use resvg::usvg::{self, fontdb};
+
+/// Load the system fonts into `opt`.
+///
+/// usvg asks for the generic families by the usual Windows and Mac font
+/// names (Times New Roman, Courier New, ...), and draws nothing if those
+/// aren't installed, so point any that are missing at fonts that are.
+pub fn load(opt: &mut usvg::Options) {
+    let fontdb = opt.fontdb_mut();
+    fontdb.load_system_fonts();
+
+    let missing = |fontdb: &fontdb::Database, family| {
+        let query = fontdb::Query {
+            families: &[family],
+            ..Default::default()
+        };
+        fontdb.query(&query).is_none()
+    };
+    let any = |fontdb: &fontdb::Database, monospaced: bool| {
+        let face = fontdb
+            .faces()
+            .find(|face| face.monospaced == monospaced)
+            .or_else(|| fontdb.faces().next())?;
+        Some(face.families.first()?.0.clone())
+    };
+
+    if missing(fontdb, fontdb::Family::Serif) {
+        if let Some(family) = any(fontdb, false) {
+            fontdb.set_serif_family(family);
+        }
+    }
+    if missing(fontdb, fontdb::Family::SansSerif) {
+        if let Some(family) = any(fontdb, false) {
+            fontdb.set_sans_serif_family(family);
+        }
+    }
+    if missing(fontdb, fontdb::Family::Monospace) {
+        if let Some(family) = any(fontdb, true) {
+            fontdb.set_monospace_family(family);
+        }
+    }
+}
+
+/// Add `c` to `svg` the way the printer would print it, centred in `cells`
+/// character cells of `font_width` dots from `(left, top)`. Whitespace draws
+/// nothing.
+pub fn glyph(
+    svg: &mut String,
+    (left, top): (u32, u32),
+    c: char,
+    cells: u32,
+    bold: bool,
+    font_width: u32,
+) {
+    if c.is_whitespace() {
+        return;
+    }
+    let char_height = font_width * 2;
+    let _ = write!(
+        svg,
+        r#"<text x="{x}" y="{y}" font-family="monospace" font-size="{size}" font-weight="{weight}" text-anchor="middle">&#x{code:x};</text>"#,
+        x = left as f32 + (cells * font_width) as f32 / 2.0,
+        y = top + char_height * 4 / 5,
+        size = font_width as f32 * 5.0 / 3.0,
+        weight = if bold { "bold" } else { "normal" },
+        code = u32::from(c),
+    );
+}
+
+/// Add an underline `thickness` dots thick to `svg`, under `width` dots of
+/// text that starts at `(left, top)`.
+pub fn underline(
+    svg: &mut String,
+    (left, top): (u32, u32),
+    width: u32,
+    thickness: u32,
+    font_width: u32,
+) {
+    let char_height = font_width * 2;
+    let _ = write!(
+        svg,
+        r#"<rect x="{left}" y="{y}" width="{width}" height="{thickness}"/>"#,
+        y = top + char_height - thickness,
+    );
+}
@@ -0,0 +1,256 @@
+//! Draws the grid straight from the puzzle's cells, at the printer's own resolution.
+//!
+//! Everything is placed on whole dots, so lines come out exactly `line_width`
+//! dots thick and the clue numbers are bitmap digits rather than scaled text.
+
+use resvg::tiny_skia::{
+    Color, IntRect, Paint, PathBuilder, Pixmap, PixmapPaint, Rect, Stroke, Transform,
+};
+
+use crate::puzzle::{CellKind, Puzzle};
+
+/// 5x7 bitmaps of the digits 0-9, one row per byte with the leftmost dot in bit 4.
+#[rustfmt::skip]
+const DIGITS: [[u8; 7]; 10] = [
+    [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+    [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+    [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+    [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+    [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+    [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+    [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+    [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+    [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+];
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// Render the grid centered in an image `width` dots wide. Returns `None` if
+/// the puzzle doesn't include its cells, or they don't fit.
+pub fn render(puzzle: &Puzzle, width: u32, line_width: u32) -> Option<Pixmap> {
+    let columns = puzzle.dimensions.width as u32;
+    let cell = width.checked_sub(line_width)? / columns.max(1);
+    draw(puzzle, width, cell, line_width)
+}
+
+/// Render the grid with squares `cell` dots across, centered in an image at
+/// least `width` dots wide. A grid wider than that is meant to be cut up with
+/// [`tile`].
+pub fn render_cells(puzzle: &Puzzle, width: u32, cell: u32, line_width: u32) -> Option<Pixmap> {
+    let columns = puzzle.dimensions.width as u32;
+    let width = width.max(cell * columns + line_width);
+    draw(puzzle, width, cell, line_width)
+}
+
+/// Draw the grid with squares `cell` dots across, centered in an image
+/// `width` dots wide.
+fn draw(puzzle: &Puzzle, width: u32, cell: u32, line_width: u32) -> Option<Pixmap> {
+    let columns = puzzle.dimensions.width as u32;
+    let rows = puzzle.cells.len() as u32 / columns.max(1);
+    if puzzle.cells.is_empty() || columns == 0 {
+        return None;
+    }
+    if cell <= 2 * line_width {
+        return None;
+    }
+    let left = width.checked_sub(cell * columns + line_width)? / 2;
+    let mut canvas = Canvas::new(width, cell * rows + line_width)?;
+
+    // scale the numbers up with the cell, keeping them to about a third of its height
+    let scale = (cell / 25).max(1);
+    let inset = line_width + scale;
+
+    for (i, c) in puzzle.cells.iter().enumerate() {
+        let (row, column) = (i as u32 / columns, i as u32 % columns);
+        let (x, y) = (left + column * cell, row * cell);
+        let size = cell + line_width;
+        match c.kind {
+            CellKind::Void => continue,
+            CellKind::Block => {
+                canvas.fill(x, y, size, size);
+                continue;
+            }
+            CellKind::Shaded => canvas.shade(x + line_width, y + line_width, cell - line_width),
+            CellKind::Circled => canvas.circle(x, y, size, line_width),
+            CellKind::Normal => {}
+        }
+        canvas.fill(x, y, size, line_width);
+        canvas.fill(x, y, line_width, size);
+        canvas.fill(x, y + cell, size, line_width);
+        canvas.fill(x + cell, y, line_width, size);
+
+        if let Some(label) = &c.label {
+            canvas.number(x + inset, y + inset, label, scale);
+        }
+    }
+    Some(canvas.pixmap)
+}
+
+/// How far each strip from [`tile`] overlaps the next, in dots.
+pub const OVERLAP: u32 = 16;
+/// Height of the margins above and below a tiled strip, for its alignment
+/// marks.
+const MARGIN: u32 = 16;
+
+/// Cut an image wider than the paper into strips up to `width` dots wide, to be
+/// printed one after the other and taped together side by side.
+///
+/// Neighbouring strips share [`OVERLAP`] dots of the image, and a cross in
+/// the margins above and below marks the middle of each shared band, so the
+/// strips line up when one is laid over the next with the crosses on top of
+/// each other. An image that already fits comes back as it is.
+pub fn tile(image: &Pixmap, width: u32) -> Option<Vec<Pixmap>> {
+    if image.width() <= width {
+        return Some(vec![image.clone()]);
+    }
+    let widest = width.checked_sub(OVERLAP).filter(|&step| step > 0)?;
+    let strips = (image.width() - OVERLAP).div_ceil(widest);
+    // share the image out evenly, so the last strip isn't a sliver
+    let step = (image.width() - OVERLAP).div_ceil(strips);
+    let height = image.height() + 2 * MARGIN;
+    (0..strips)
+        .map(|i| {
+            let left = i * step;
+            let part = (step + OVERLAP).min(image.width() - left);
+            let part =
+                image.clone_rect(IntRect::from_xywh(left as i32, 0, part, image.height())?)?;
+            // printers take images in whole bytes
+            let mut canvas = Canvas::new((step + OVERLAP).next_multiple_of(8), height)?;
+            canvas.pixmap.draw_pixmap(
+                0,
+                MARGIN as i32,
+                part.as_ref(),
+                &PixmapPaint::default(),
+                Transform::identity(),
+                None,
+            );
+            // the bands shared with the strips before and after this one
+            let seams = [
+                i.checked_sub(1).map(|_| left),
+                (i + 1 < strips).then(|| left + step),
+            ];
+            for seam in seams.into_iter().flatten() {
+                let x = seam + OVERLAP / 2 - left;
+                canvas.cross(x, MARGIN / 2);
+                canvas.cross(x, height - MARGIN / 2);
+            }
+            Some(canvas.pixmap)
+        })
+        .collect()
+}
+
+struct Canvas {
+    pixmap: Pixmap,
+    black: Paint<'static>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Option<Canvas> {
+        let mut pixmap = Pixmap::new(width, height)?;
+        pixmap.fill(Color::WHITE);
+        let mut black = Paint::default();
+        black.set_color(Color::BLACK);
+        black.anti_alias = false;
+        Some(Canvas { pixmap, black })
+    }
+
+    fn fill(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        if let Some(rect) = Rect::from_xywh(x as f32, y as f32, width as f32, height as f32) {
+            self.pixmap
+                .fill_rect(rect, &self.black, Transform::identity(), None);
+        }
+    }
+
+    /// Thermal printers can't print grey, so shade with a sparse dot pattern
+    /// that stays light enough to read the number on top of it.
+    fn shade(&mut self, x: u32, y: u32, size: u32) {
+        for dy in (0..size).step_by(2) {
+            for dx in (0..size).step_by(2) {
+                self.fill(x + dx + dy % 4 / 2, y + dy, 1, 1);
+            }
+        }
+    }
+
+    fn circle(&mut self, x: u32, y: u32, size: u32, line_width: u32) {
+        let center = size as f32 / 2.0;
+        let radius = center - line_width as f32 - 1.0;
+        let Some(path) = PathBuilder::from_circle(x as f32 + center, y as f32 + center, radius)
+        else {
+            return;
+        };
+        let stroke = Stroke {
+            width: 1.0,
+            ..Stroke::default()
+        };
+        self.pixmap
+            .stroke_path(&path, &self.black, &stroke, Transform::identity(), None);
+    }
+
+    /// A cross centered on `(x, y)` that fits in the margin of a tiled strip.
+    fn cross(&mut self, x: u32, y: u32) {
+        let arm = MARGIN / 2 - 2;
+        self.fill(x - arm, y - 1, 2 * arm + 1, 2);
+        self.fill(x - 1, y - arm, 2, 2 * arm + 1);
+    }
+
+    fn number(&mut self, x: u32, y: u32, label: &str, scale: u32) {
+        let digits = label.bytes().filter(u8::is_ascii_digit);
+        for (i, digit) in digits.enumerate() {
+            let glyph = &DIGITS[usize::from(digit - b'0')];
+            let left = x + i as u32 * (GLYPH_WIDTH + 1) * scale;
+            for (row, bits) in (0..GLYPH_HEIGHT).zip(glyph) {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        self.fill(left + column * scale, y + row * scale, scale, scale);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_black(pixmap: &Pixmap, x: u32, y: u32) -> bool {
+        pixmap.pixel(x, y).unwrap().red() < 128
+    }
+
+    #[test]
+    fn tiles_with_overlap_and_marks() {
+        // a white image with a black column every 100 dots
+        let mut image = Canvas::new(1000, 200).unwrap();
+        for x in (0..1000).step_by(100) {
+            image.fill(x, 0, 1, 200);
+        }
+        let strips = tile(&image.pixmap, 384).unwrap();
+        // 984 dots past the first overlap, shared out as 328 per strip
+        assert_eq!(strips.len(), 3);
+        for strip in &strips {
+            assert_eq!((strip.width(), strip.height()), (344, 200 + 2 * MARGIN));
+        }
+
+        // the image starts below the top margin
+        assert!(!is_black(&strips[0], 300, MARGIN - 1));
+        assert!(is_black(&strips[0], 300, MARGIN));
+        // each strip picks up where the last one's shared band starts
+        assert!(is_black(&strips[1], 400 - 328, MARGIN + 10));
+        // crosses over the middle of the band they share, in both strips
+        let seam = 328 + OVERLAP / 2;
+        assert!(is_black(&strips[0], seam, MARGIN / 2));
+        assert!(is_black(&strips[1], seam - 328, MARGIN / 2));
+        assert!(is_black(&strips[1], seam - 328, 200 + MARGIN + MARGIN / 2));
+        // and the first strip has nothing on its left edge
+        assert!(!is_black(&strips[0], OVERLAP / 2, MARGIN / 2));
+    }
+
+    #[test]
+    fn leaves_narrow_images_alone() {
+        let image = Canvas::new(384, 100).unwrap().pixmap;
+        let strips = tile(&image, 384).unwrap();
+        assert_eq!(strips.len(), 1);
+        assert_eq!(strips[0].height(), 100);
+    }
+}
@@ -0,0 +1,285 @@
+//! The receipt drawn as one picture and turned on its side, so that the grid
+//! and the clues sit next to each other along the length of the paper rather
+//! than one after the other across it.
+//!
+//! Text is drawn with the system fonts a character per cell of the printer's
+//! font, like the preview, and the clues flow down columns as wide as an
+//! ordinary receipt.
+
+use resvg::tiny_skia::{Color, Pixmap, PixmapPaint, Transform};
+use resvg::usvg;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::config::{GridRenderer, Layout, Section};
+use crate::error::Error;
+use crate::fonts;
+use crate::grid;
+use crate::puzzle::{Crossword, Puzzle};
+use crate::receipt::{clue_entries, format_list, render_board};
+use crate::styled::{self, Style, Styled};
+
+/// A line of text, in runs of the same style. Empty for a blank line.
+type Line = Vec<(Style, String)>;
+
+/// Draw the header, grid, clues and byline on a page as tall as the paper is
+/// wide and as long as they need, then turn it a quarter clockwise so it can
+/// be printed like any other image. The solution isn't included.
+pub fn render(crossword: &Crossword, layout: &Layout) -> Result<Pixmap, Error> {
+    crossword.check()?;
+    let puzzle = &crossword.body[0];
+    let height = u32::from(layout.paper.image_width());
+    let font_width = u32::from(layout.font_width.max(1));
+    let line_height = font_width * 5 / 2;
+    let margin = font_width;
+    let gap = 2 * font_width;
+
+    let mut header = Vec::new();
+    if layout.shows(Section::Header) {
+        let heading = layout.header(crossword);
+        if !heading.is_empty() {
+            header.push(plain(heading));
+        }
+        if let Some(date) = crossword.publication_date {
+            header.push(plain(&date.strftime("%A, %B %-d, %Y").to_string()));
+        }
+        header.extend(
+            crossword
+                .title
+                .as_deref()
+                .filter(|&t| t != heading)
+                .map(plain),
+        );
+    }
+    // the grid goes under the header, as big as the rest of the height allows
+    let grid_top = margin + header.len() as u32 * line_height;
+    let grid = if layout.shows(Section::Grid) {
+        let space = height.saturating_sub(grid_top + margin);
+        Some(render_grid(puzzle, layout, space)?)
+    } else {
+        None
+    };
+    let block_width = header
+        .iter()
+        .map(|line| line_width(line) as u32 * font_width)
+        .chain(grid.as_ref().map(Pixmap::width))
+        .max()
+        .unwrap_or(0);
+
+    let column_chars = usize::from(layout.chars_per_line());
+    let rows = (height.saturating_sub(2 * margin) / line_height).max(1) as usize;
+    let columns = flow(text_lines(crossword, layout, column_chars), rows);
+    let column_width = column_chars as u32 * font_width;
+    let text_left = match block_width {
+        0 => margin,
+        _ => margin + block_width + gap,
+    };
+    let right = match columns.len() as u32 {
+        0 => margin + block_width,
+        n => text_left + n * (column_width + gap) - gap,
+    };
+    let width = right + margin;
+
+    let empty = || Error::EmptyCanvas {
+        width: width as f32,
+        height: height as f32,
+    };
+    let mut page = Pixmap::new(width, height).ok_or_else(empty)?;
+    page.fill(Color::WHITE);
+
+    let mut svg =
+        format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}">"#);
+    for (i, line) in header.iter().enumerate() {
+        draw_line(
+            &mut svg,
+            margin,
+            margin + i as u32 * line_height,
+            line,
+            font_width,
+        );
+    }
+    for (i, column) in columns.iter().enumerate() {
+        let left = text_left + i as u32 * (column_width + gap);
+        for (j, line) in column.iter().enumerate() {
+            draw_line(
+                &mut svg,
+                left,
+                margin + j as u32 * line_height,
+                line,
+                font_width,
+            );
+        }
+    }
+    svg.push_str("</svg>");
+    let mut opt = usvg::Options::default();
+    fonts::load(&mut opt);
+    let tree = usvg::Tree::from_str(&svg, &opt)?;
+    resvg::render(&tree, Transform::identity(), &mut page.as_mut());
+
+    if let Some(grid) = grid {
+        page.draw_pixmap(
+            margin as i32,
+            grid_top as i32,
+            grid.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+    }
+    rotate(&page).ok_or_else(empty)
+}
+
+/// Draw the grid to fit in a square `space` dots across.
+fn render_grid(puzzle: &Puzzle, layout: &Layout, space: u32) -> Result<Pixmap, Error> {
+    let columns = puzzle.dimensions.width.max(1) as u32;
+    let rows = (puzzle.cells.len() as u32 / columns).max(1);
+    // a grid taller than it is wide has to be narrower to fit the height
+    let width = space * columns / columns.max(rows);
+    let native = match layout.grid {
+        GridRenderer::Svg if !puzzle.board.is_empty() => None,
+        _ => grid::render(puzzle, width, layout.line_width),
+    };
+    match native {
+        Some(grid) => Ok(grid),
+        None => render_board(&puzzle.board, width as f32, layout.dpi),
+    }
+}
+
+/// The clue lists and the byline, wrapped to `width` characters, with a blank
+/// line after each part.
+fn text_lines(crossword: &Crossword, layout: &Layout, width: usize) -> Vec<Line> {
+    let puzzle = &crossword.body[0];
+    let mut lines = Vec::new();
+
+    if layout.shows(Section::Clues) {
+        for clues in &puzzle.clue_lists {
+            lines.push(plain(&format!("{:?}:", clues.name)));
+            for entry in clue_entries(crossword, clues, layout) {
+                lines.extend(wrap(&entry.text, width, &entry.label, &entry.indent));
+            }
+            lines.push(Vec::new());
+        }
+    }
+
+    if layout.shows(Section::Byline) {
+        if !crossword.constructors.is_empty() {
+            let by = Styled::plain(&format_list(&crossword.constructors));
+            lines.extend(wrap(&by, width, "By ", "   "));
+        }
+        if !crossword.editor.is_empty() {
+            let edited = Styled::plain(&format!("Edited by {}", crossword.editor));
+            lines.extend(wrap(&edited, width, "", ""));
+        }
+    }
+    lines
+}
+
+/// Split `lines` into columns of up to `rows` lines, leaving out blank lines
+/// that would start a column.
+fn flow(lines: Vec<Line>, rows: usize) -> Vec<Vec<Line>> {
+    let mut columns: Vec<Vec<Line>> = Vec::new();
+    for line in lines {
+        match columns.last_mut() {
+            Some(column) if column.len() < rows => column.push(line),
+            _ if line.is_empty() => {}
+            _ => columns.push(vec![line]),
+        }
+    }
+    // don't leave a blank column at the end
+    if let Some(column) = columns.last_mut() {
+        while column.last().is_some_and(Vec::is_empty) {
+            column.pop();
+        }
+    }
+    columns
+}
+
+/// [`styled::wrap`], keeping the lines.
+fn wrap(text: &Styled, width: usize, initial_indent: &str, subsequent_indent: &str) -> Vec<Line> {
+    styled::wrap(text, width, initial_indent, subsequent_indent)
+        .into_iter()
+        .map(|line| {
+            line.into_iter()
+                .map(|(style, s)| (style, s.to_owned()))
+                .collect()
+        })
+        .collect()
+}
+
+fn plain(text: &str) -> Line {
+    vec![(Style::default(), text.to_owned())]
+}
+
+fn line_width(line: &Line) -> usize {
+    line.iter().map(|(_, s)| s.width()).sum()
+}
+
+/// Add `line` to `svg` with its top left corner at `(left, top)`, a character
+/// per `font_width` dots.
+fn draw_line(svg: &mut String, left: u32, top: u32, line: &Line, font_width: u32) {
+    let mut x = left;
+    for (style, s) in line {
+        let start = x;
+        let bold = style.bold || style.double_strike;
+        for c in s.chars() {
+            let cells = c.width().unwrap_or(0) as u32;
+            fonts::glyph(svg, (x, top), c, cells, bold, font_width);
+            x += cells * font_width;
+        }
+        if style.underline && x > start {
+            fonts::underline(svg, (start, top), x - start, 1, font_width);
+        }
+    }
+}
+
+/// Turn `page` a quarter clockwise, so its left edge comes off the printer
+/// first and its top runs down the right of the paper.
+fn rotate(page: &Pixmap) -> Option<Pixmap> {
+    let (width, height) = (page.width(), page.height());
+    let mut turned = Pixmap::new(height, width)?;
+    let pixels = page.pixels();
+    let turned_pixels = turned.pixels_mut();
+    for y in 0..height {
+        for x in 0..width {
+            let to = x * height + (height - 1 - y);
+            turned_pixels[to as usize] = pixels[(y * width + x) as usize];
+        }
+    }
+    Some(turned)
+}
+
+#[cfg(test)]
+mod tests {
+    use resvg::tiny_skia::ColorU8;
+
+    use super::*;
+    use crate::paper::Paper;
+
+    #[test]
+    fn turns_clockwise() {
+        // a 3x2 page with only its top left corner black
+        let mut page = Pixmap::new(3, 2).unwrap();
+        page.fill(Color::WHITE);
+        page.pixels_mut()[0] = ColorU8::from_rgba(0, 0, 0, 255).premultiply();
+
+        let turned = rotate(&page).unwrap();
+        assert_eq!((turned.width(), turned.height()), (2, 3));
+        let black: Vec<_> = turned.pixels().iter().map(|p| p.red() == 0).collect();
+        assert_eq!(black, [false, true, false, false, false, false]);
+    }
+
+    #[test]
+    fn fills_the_paper_width() {
+        let json = include_str!("../tests/fixtures/daily-2024-03-04.json");
+        let crossword: Crossword = serde_json::from_str(json).unwrap();
+        for paper in [Paper::MM58, Paper::MM80] {
+            let layout = Layout {
+                paper,
+                landscape: true,
+                ..Layout::default()
+            };
+            let image = render(&crossword, &layout).unwrap();
+            assert_eq!(image.width(), u32::from(paper.image_width()));
+            assert!(image.height() > image.width() * 2, "{}", image.height());
+        }
+    }
+}
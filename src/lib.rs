@@ -0,0 +1,162 @@
+//! Print the NYT crosswords on an ESC/POS receipt printer.
+//!
+//! The `miniprint` binary is a thin wrapper around [`run`]. To print from
+//! somewhere else, download a puzzle with [`fetch::download`], parse it into a
+//! [`Crossword`] and hand it to a [`Receipt`] along with any
+//! [`escpos`] driver. Across Lite files can be read with [`puz::parse`]
+//! instead.
+
+pub mod cache;
+pub mod charset;
+pub mod cli;
+pub mod config;
+pub mod decode;
+pub mod driver;
+mod error;
+pub mod fetch;
+mod fonts;
+pub mod grid;
+pub mod landscape;
+pub mod paper;
+pub mod preview;
+pub mod puz;
+pub mod puzzle;
+pub mod receipt;
+mod styled;
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+pub use error::Error;
+pub use puzzle::{Clue, Crossword, MiniCrossword, Puzzle, Variant};
+pub use receipt::Receipt;
+
+use cache::Cache;
+use cli::{Cli, Command, FetchArgs, PrintArgs, PuzzleArgs};
+use config::{Config, Download, Settings};
+use driver::{MemoryDriver, Output};
+
+/// Run the command line.
+pub fn run(cli: Cli) -> Result<(), Error> {
+    match cli.command {
+        None => print(cli.print, cli.config.as_deref()),
+        Some(Command::Print(args)) => print(args, cli.config.as_deref()),
+        Some(Command::Fetch(args)) => fetch(args, cli.config.as_deref()),
+    }
+}
+
+/// Get the puzzle JSON from the cache, or download and cache it. If today's
+/// puzzle can't be downloaded, fall back to the latest one in the cache.
+fn load_json(args: &PuzzleArgs, download: &Download) -> Result<String, Error> {
+    let cache = Cache::open(download.variant);
+    let date = args.date.unwrap_or_else(fetch::today);
+    let first = download.variant.first();
+    if date < first {
+        return Err(Error::TooEarly {
+            puzzle: download.variant.title(),
+            first,
+        });
+    }
+
+    if let (Some(cache), false) = (&cache, args.refresh) {
+        // a cache that can't be read is only a reason to download again
+        if let Some(json) = cache.get(date).unwrap_or_else(|e| {
+            eprintln!("warning: couldn't read the cached puzzle: {e}");
+            None
+        }) {
+            return Ok(json);
+        }
+        if args.offline && args.date.is_none() {
+            if let Some(json) = cache.latest()? {
+                return Ok(json);
+            }
+        }
+    }
+    if args.offline {
+        return Err(Error::NotCached(date));
+    }
+
+    let retrying = |e: &Error, backoff: Duration| {
+        eprintln!("warning: {e}; retrying in {}s", backoff.as_secs_f32());
+    };
+    let json = match (fetch::download(args.date, download, retrying), &cache) {
+        (Ok(json), _) => json,
+        (Err(e), Some(cache)) if args.date.is_none() => match cache.latest()? {
+            Some(json) => {
+                eprintln!("warning: {e}; using the latest cached puzzle instead");
+                return Ok(json);
+            }
+            None => return Err(e),
+        },
+        (Err(e), _) => return Err(e),
+    };
+    // make sure it's actually a puzzle before caching it
+    let crossword: Crossword = serde_json::from_str(&json)?;
+    crossword.check()?;
+    if let Some(cache) = &cache {
+        if let Err(e) = cache.put(crossword.publication_date.unwrap_or(date), &json) {
+            eprintln!("warning: couldn't cache puzzle: {e}");
+        }
+    }
+    Ok(json)
+}
+
+fn fetch(args: FetchArgs, config: Option<&Path>) -> Result<(), Error> {
+    let download = Download::new(&args.puzzle, Config::load(config)?.download);
+    let json = load_json(&args.puzzle, &download)?;
+    if args.output == Path::new("-") {
+        io::stdout().lock().write_all(json.as_bytes())?;
+    } else {
+        File::create(&args.output)
+            .and_then(|mut file| file.write_all(json.as_bytes()))
+            .map_err(|source| Error::File {
+                path: args.output,
+                source,
+            })?;
+    }
+    Ok(())
+}
+
+fn print(args: PrintArgs, config: Option<&Path>) -> Result<(), Error> {
+    let config = Config::load(config)?;
+    let settings = Settings::new(&args, config);
+
+    let data = match &args.input {
+        Some(path) if path == Path::new("-") => {
+            let mut data = Vec::new();
+            io::stdin().lock().read_to_end(&mut data)?;
+            data
+        }
+        Some(path) => fs::read(path).map_err(|source| Error::File {
+            path: path.clone(),
+            source,
+        })?,
+        None => load_json(&args.puzzle, &settings.download)?.into_bytes(),
+    };
+    let crossword = if puz::is_puz(&data) {
+        puz::parse(&data)?
+    } else {
+        serde_json::from_slice(&data)?
+    };
+
+    let memory = MemoryDriver::default();
+    let output = if args.dry_run {
+        Output::Memory(memory.clone())
+    } else {
+        Output::open(&settings)?
+    };
+    Receipt::new(&crossword)
+        .layout(settings.layout)
+        .cut(settings.cut)
+        .code_pages(settings.code_pages)
+        .raster_glyphs(settings.raster_glyphs)
+        .print(output)?;
+
+    if args.dry_run {
+        let transcript = decode::transcript(&memory.data());
+        io::stdout().lock().write_all(transcript.as_bytes())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// The printable width of a roll of receipt paper.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "Width")]
+pub struct Paper {
+    /// Width of the print head in dots.
+    pub dots: u16,
+}
+
+impl Paper {
+    pub const MM58: Paper = Paper { dots: 384 };
+    pub const MM80: Paper = Paper { dots: 576 };
+
+    /// The widest image that fits, since raster images are sent a byte (8 dots) at a time.
+    pub fn image_width(self) -> u16 {
+        self.dots / 8 * 8
+    }
+
+    /// How many characters of the given width fit on a line.
+    pub fn chars_per_line(self, font_width: u8) -> u8 {
+        let chars = self.dots / u16::from(font_width.max(1));
+        chars.try_into().unwrap_or(u8::MAX)
+    }
+}
+
+impl FromStr for Paper {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "58mm" => Ok(Paper::MM58),
+            "80mm" => Ok(Paper::MM80),
+            _ => match s.parse() {
+                Ok(dots) if dots < 8 => Err("paper must be at least 8 dots wide".to_owned()),
+                Ok(dots) => Ok(Paper { dots }),
+                Err(_) => Err(format!(
+                    "unknown paper {s:?}, expected 58mm, 80mm or a width in dots"
+                )),
+            },
+        }
+    }
+}
+
+/// How the paper is given in the config: `"58mm"`, `"80mm"`, or a width in
+/// dots, which can be a number or a string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Width {
+    Dots(u16),
+    Name(String),
+}
+
+impl TryFrom<Width> for Paper {
+    type Error = String;
+
+    fn try_from(width: Width) -> Result<Self, Self::Error> {
+        match width {
+            Width::Dots(dots) => dots.to_string().parse(),
+            Width::Name(s) => s.parse(),
+        }
+    }
+}
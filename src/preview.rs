@@ -0,0 +1,416 @@
+//! Draws the receipt instead of printing it, from the same ESC/POS stream the
+//! printer would get, so layout changes can be checked without using paper.
+//!
+//! Text is drawn a character per cell of the printer's font (`font_width`
+//! dots wide and twice as tall), and everything ends up as black or white
+//! dots like it would on a thermal printer.
+
+use std::cell::{Cell as StdCell, RefCell};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use escpos::driver::Driver;
+use escpos::errors::{self, PrinterError};
+use resvg::tiny_skia::{Color, Paint, Pixmap, Rect, Transform};
+use resvg::usvg;
+
+use crate::charset::CodePage;
+use crate::config::Layout;
+use crate::decode::{decode, Command};
+use crate::fonts;
+
+/// Collects the ESC/POS stream and renders it to a PNG, or a PDF if the path
+/// ends in `.pdf`, once the receipt is done: when it's flushed after a cut,
+/// or else when the driver is dropped.
+pub struct PreviewDriver {
+    path: PathBuf,
+    width: u32,
+    font_width: u32,
+    dpi: f32,
+    data: RefCell<Vec<u8>>,
+    /// Whether the last thing written was a cut.
+    cut: StdCell<bool>,
+    /// How much of `data` the saved preview shows.
+    saved: StdCell<usize>,
+}
+
+impl PreviewDriver {
+    pub fn open(path: &Path, layout: &Layout) -> PreviewDriver {
+        PreviewDriver {
+            path: path.to_owned(),
+            width: layout.paper.dots.into(),
+            font_width: layout.font_width.max(1).into(),
+            dpi: layout.dpi,
+            data: RefCell::new(Vec::new()),
+            cut: StdCell::new(false),
+            saved: StdCell::new(0),
+        }
+    }
+
+    /// Draw everything written so far and write it to the file.
+    fn render(&self) -> errors::Result<()> {
+        let data = self.data.try_borrow_mut()?;
+        let mut receipt = Receipt::new(self.width, self.font_width);
+        for command in decode(&data) {
+            receipt.run(command);
+        }
+        self.saved.set(data.len());
+        self.save(&receipt).map_err(|e| {
+            PrinterError::Io(format!(
+                "couldn't write preview {}: {e}",
+                self.path.display()
+            ))
+        })
+    }
+
+    fn save(&self, receipt: &Receipt) -> Result<(), String> {
+        let is_pdf = self
+            .path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"));
+        if is_pdf {
+            fs::write(&self.path, receipt.pdf(self.dpi)).map_err(|e| e.to_string())
+        } else {
+            receipt
+                .pixmap()?
+                .save_png(&self.path)
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+impl Driver for PreviewDriver {
+    fn name(&self) -> String {
+        format!("preview ({})", self.path.display())
+    }
+
+    fn write(&self, data: &[u8]) -> errors::Result<()> {
+        // the printer writes a command at a time, so a cut comes on its own
+        let cut = matches!(decode(data).last(), Some(Command::Cut { .. }));
+        self.cut.set(cut);
+        self.data.try_borrow_mut()?.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> errors::Result<usize> {
+        Ok(0)
+    }
+
+    fn flush(&self) -> errors::Result<()> {
+        if self.cut.get() {
+            self.render()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PreviewDriver {
+    /// Save a receipt that didn't end with a cut, since nothing else says
+    /// it's done.
+    fn drop(&mut self) {
+        let unsaved = self.data.get_mut().len() > self.saved.get();
+        if unsaved {
+            if let Err(e) = self.render() {
+                eprintln!("warning: {e}");
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Glyph {
+    Char(char),
+    /// A user-defined character
+    User(u8),
+}
+
+#[derive(Clone, Copy)]
+struct Cell {
+    glyph: Glyph,
+    bold: bool,
+    underline: u8,
+}
+
+/// A user-defined character, as columns of `height` bytes.
+struct UserCharacter {
+    height: u32,
+    data: Vec<u8>,
+}
+
+/// The modes that ESC @ resets.
+#[derive(Default)]
+struct State {
+    bold: bool,
+    underline: u8,
+    upside_down: bool,
+    justify: u8,
+    page: CodePage,
+    user_characters: HashMap<u8, UserCharacter>,
+    use_user_characters: bool,
+}
+
+/// The printer's state and the dots printed so far.
+struct Receipt {
+    width: u32,
+    font_width: u32,
+    line_height: u32,
+    /// One entry per dot, a row at a time.
+    dots: Vec<bool>,
+    line: Vec<Cell>,
+    state: State,
+    opt: usvg::Options<'static>,
+}
+
+impl Receipt {
+    fn new(width: u32, font_width: u32) -> Receipt {
+        let mut opt = usvg::Options::default();
+        fonts::load(&mut opt);
+        Receipt {
+            width,
+            font_width,
+            // the default line spacing leaves a little room between lines
+            line_height: font_width * 5 / 2,
+            dots: Vec::new(),
+            line: Vec::new(),
+            state: State::default(),
+            opt,
+        }
+    }
+
+    fn height(&self) -> u32 {
+        self.dots.len() as u32 / self.width
+    }
+
+    fn run(&mut self, command: Command<'_>) {
+        let state = &mut self.state;
+        match command {
+            Command::Text(text) => {
+                for &b in text {
+                    self.push(b);
+                }
+            }
+            Command::Feed(lines) => {
+                for _ in 0..lines {
+                    self.print_line();
+                }
+            }
+            Command::Init => *state = State::default(),
+            Command::Cancel => self.line.clear(),
+            Command::Bold(on) | Command::DoubleStrike(on) => state.bold = on,
+            Command::Underline(mode) => state.underline = mode,
+            Command::UpsideDown(on) => state.upside_down = on,
+            Command::Justify(justify) => state.justify = justify,
+            Command::PageCode(n) => state.page = CodePage::from_number(n).unwrap_or(state.page),
+            Command::DefineCharacters(chars) => {
+                for c in chars {
+                    let user = UserCharacter {
+                        height: c.height.into(),
+                        data: c.data.to_vec(),
+                    };
+                    state.user_characters.insert(c.code, user);
+                }
+            }
+            Command::UserCharacters(on) => state.use_user_characters = on,
+            Command::Image {
+                width_bytes,
+                height,
+                data,
+            } => self.image(u32::from(width_bytes) * 8, height.into(), data),
+            Command::Cut { .. } => self.cut(),
+            Command::Unknown(_) => {}
+        }
+    }
+
+    fn push(&mut self, b: u8) {
+        let state = &self.state;
+        let glyph = if state.use_user_characters && state.user_characters.contains_key(&b) {
+            Glyph::User(b)
+        } else {
+            Glyph::Char(state.page.char(b).unwrap_or('?'))
+        };
+        // the printer wraps on its own once the line is full
+        if (self.line.len() as u32 + 1) * self.font_width > self.width {
+            self.print_line();
+        }
+        self.line.push(Cell {
+            glyph,
+            bold: self.state.bold,
+            underline: self.state.underline,
+        });
+    }
+
+    /// Where to start something `width` dots wide on the current justification.
+    fn left(&self, width: u32) -> u32 {
+        let space = self.width.saturating_sub(width);
+        match self.state.justify {
+            1 => space / 2,
+            2 => space,
+            _ => 0,
+        }
+    }
+
+    fn print_line(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        let Some(mut band) = Pixmap::new(self.width, self.line_height) else {
+            return;
+        };
+        let left = self.left(line.len() as u32 * self.font_width);
+        let cell_left = |i: usize| left + i as u32 * self.font_width;
+
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}">"#,
+            self.width, self.line_height
+        );
+        for (i, cell) in line.iter().enumerate() {
+            let x = cell_left(i);
+            if let Glyph::Char(c) = cell.glyph {
+                fonts::glyph(&mut svg, (x, 0), c, 1, cell.bold, self.font_width);
+            }
+            if cell.underline > 0 {
+                let thickness = u32::from(cell.underline);
+                fonts::underline(
+                    &mut svg,
+                    (x, 0),
+                    self.font_width,
+                    thickness,
+                    self.font_width,
+                );
+            }
+        }
+        svg.push_str("</svg>");
+        if let Ok(tree) = usvg::Tree::from_str(&svg, &self.opt) {
+            resvg::render(&tree, Transform::identity(), &mut band.as_mut());
+        }
+
+        let mut black = Paint::default();
+        black.set_color(Color::BLACK);
+        let mut fill = |x: u32, y: u32, width: u32, height: u32| {
+            if let Some(rect) = Rect::from_xywh(x as f32, y as f32, width as f32, height as f32) {
+                band.fill_rect(rect, &black, Transform::identity(), None);
+            }
+        };
+        for (i, cell) in line.iter().enumerate() {
+            let x = cell_left(i);
+            if let Glyph::User(code) = cell.glyph {
+                let user = &self.state.user_characters[&code];
+                for (column, bytes) in user.data.chunks(user.height as usize).enumerate() {
+                    for (row, byte) in bytes.iter().enumerate() {
+                        for bit in 0..8 {
+                            if byte & (0x80 >> bit) != 0 {
+                                fill(x + column as u32, row as u32 * 8 + bit, 1, 1);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if self.state.upside_down {
+            // turning the line around is the same as reversing its pixels
+            let pixels = band.pixels_mut();
+            pixels.reverse();
+        }
+        let dots = band.pixels().iter().map(|p| p.alpha() >= 128);
+        self.dots.extend(dots);
+    }
+
+    fn image(&mut self, width: u32, height: u32, data: &[u8]) {
+        let left = self.left(width);
+        let row_bytes = width.div_ceil(8) as usize;
+        for row in data.chunks(row_bytes).take(height as usize) {
+            let mut dots = vec![false; self.width as usize];
+            for x in 0..width.min(self.width - left) {
+                let byte = row[(x / 8) as usize];
+                dots[(left + x) as usize] = byte & (0x80 >> (x % 8)) != 0;
+            }
+            self.dots.extend(dots);
+        }
+    }
+
+    /// Feed to the cutter and mark where the paper is cut.
+    fn cut(&mut self) {
+        self.print_line();
+        let dashes = (0..self.width).map(|x| x % 8 < 4);
+        self.dots.extend(dashes);
+    }
+
+    fn pixmap(&self) -> Result<Pixmap, String> {
+        let mut pixmap =
+            Pixmap::new(self.width, self.height().max(1)).ok_or("the receipt is empty")?;
+        pixmap.fill(Color::WHITE);
+        for (pixel, &dot) in pixmap.pixels_mut().iter_mut().zip(&self.dots) {
+            if dot {
+                *pixel = Color::BLACK.premultiply().to_color_u8();
+            }
+        }
+        Ok(pixmap)
+    }
+
+    /// A single page PDF the size of the receipt, with the dots as a 1-bit image.
+    fn pdf(&self, dpi: f32) -> Vec<u8> {
+        let (width, height) = (self.width, self.height());
+        let mut image = Vec::new();
+        for row in self.dots.chunks(width as usize) {
+            for byte in row.chunks(8) {
+                let bits = byte
+                    .iter()
+                    .enumerate()
+                    .fold(0, |bits, (i, &dot)| bits | u8::from(dot) << (7 - i));
+                image.push(bits);
+            }
+        }
+        let points = |dots: u32| dots as f32 * 72.0 / dpi;
+        let (page_width, page_height) = (points(width), points(height));
+        let contents = format!("q {page_width:.2} 0 0 {page_height:.2} 0 0 cm /Im0 Do Q");
+
+        let objects: [Vec<u8>; 5] = [
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {page_width:.2} {page_height:.2}] \
+                 /Resources << /XObject << /Im0 4 0 R >> >> /Contents 5 0 R >>"
+            )
+            .into_bytes(),
+            // set bits are black
+            [
+                format!(
+                    "<< /Type /XObject /Subtype /Image /Width {width} /Height {height} \
+                     /ColorSpace /DeviceGray /BitsPerComponent 1 /Decode [1 0] /Length {} >>\nstream\n",
+                    image.len()
+                )
+                .as_bytes(),
+                &image,
+                b"\nendstream",
+            ]
+            .concat(),
+            format!(
+                "<< /Length {} >>\nstream\n{contents}\nendstream",
+                contents.len()
+            )
+            .into_bytes(),
+        ];
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend(format!("{} 0 obj\n", i + 1).as_bytes());
+            pdf.extend(object);
+            pdf.extend(b"\nendobj\n");
+        }
+        let xref = pdf.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(trailer, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            trailer,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1
+        );
+        pdf.extend(trailer.as_bytes());
+        pdf
+    }
+}
@@ -0,0 +1,430 @@
+//! Across Lite `.puz` files, the format most crosswords from outside the NYT
+//! are shared in, read into the same [`Crossword`] as the NYT's JSON.
+//!
+//! A `.puz` has no clue numbers, only the clues in order, so the grid is
+//! numbered the usual way to work out which clue goes where. The layout is
+//! described at <https://code.google.com/archive/p/puz/wikis/FileFormat.wiki>.
+
+use crate::charset::CodePage;
+use crate::error::Error;
+use crate::puzzle::{
+    Cell, CellKind, Clue, ClueList, ClueText, Crossword, Dimensions, Direction, MoreAnswers, Puzzle,
+};
+
+const MAGIC: &[u8] = b"ACROSS&DOWN\0";
+const HEADER_LEN: usize = 0x34;
+/// XORed with the checksums to make the "masked" ones in the header.
+const MASK: &[u8; 8] = b"ICHEATED";
+/// Set in the header's scrambled tag when the solution is locked with a key.
+const SCRAMBLED: u16 = 0x0004;
+/// The GEXT flag for a circled square.
+const CIRCLED: u8 = 0x80;
+
+/// Whether `data` looks like a `.puz` file rather than JSON.
+pub fn is_puz(data: &[u8]) -> bool {
+    find_header(data).is_some()
+}
+
+/// Where the header starts. Some programs put junk before it, so look for the
+/// magic string rather than expecting it at the very start.
+fn find_header(data: &[u8]) -> Option<usize> {
+    data.windows(MAGIC.len())
+        .position(|window| window == MAGIC)?
+        .checked_sub(2)
+}
+
+/// Parse a `.puz` file, after checking all of its checksums. The file doesn't
+/// say when the puzzle was published, so it has no date.
+///
+/// Rebus squares from the GRBS and RTBL sections get their whole answer, with
+/// the letter in the plain solution as another valid one, and squares marked
+/// in GEXT are circled. A scrambled solution is left out.
+pub fn parse(data: &[u8]) -> Result<Crossword, Error> {
+    let start = find_header(data).ok_or_else(|| broken("it isn't an Across Lite file"))?;
+    let mut reader = Reader { data, pos: start };
+    let header = reader.bytes(HEADER_LEN)?;
+    let word = |i: usize| u16::from_le_bytes([header[i], header[i + 1]]);
+    let (width, height) = (usize::from(header[0x2c]), usize::from(header[0x2d]));
+    let clue_count = usize::from(word(0x2e));
+    let scrambled = word(0x32) & SCRAMBLED != 0;
+    let version = &header[0x18..0x1b];
+    let size = width * height;
+
+    let solution = reader.bytes(size)?;
+    let fill = reader.bytes(size)?;
+    let strings = Strings {
+        title: reader.string()?,
+        author: reader.string()?,
+        copyright: reader.string()?,
+        clues: (0..clue_count)
+            .map(|_| reader.string())
+            .collect::<Result<_, _>>()?,
+        // older files stop after the clues
+        notes: if reader.pos < data.len() {
+            reader.string()?
+        } else {
+            &[]
+        },
+        version,
+    };
+
+    let cib = checksum(&header[0x2c..HEADER_LEN], 0);
+    let file = strings.checksum(checksum(fill, checksum(solution, cib)));
+    if word(0x00) != file {
+        return Err(broken("the file checksum is wrong"));
+    }
+    if word(0x0e) != cib {
+        return Err(broken("the header checksum is wrong"));
+    }
+    let sums = [
+        cib,
+        checksum(solution, 0),
+        checksum(fill, 0),
+        strings.checksum(0),
+    ];
+    for (i, sum) in sums.into_iter().enumerate() {
+        let [low, high] = sum.to_le_bytes();
+        if header[0x10 + i] != MASK[i] ^ low || header[0x14 + i] != MASK[i + 4] ^ high {
+            return Err(broken("the masked checksums are wrong"));
+        }
+    }
+
+    let mut rebus_grid = None;
+    let mut rebus_table = None;
+    let mut markup = None;
+    while reader.pos < data.len() {
+        let name = reader.bytes(4)?;
+        let len = usize::from(reader.word()?);
+        let sum = reader.word()?;
+        let body = reader.bytes(len)?;
+        reader.bytes(1)?;
+        let name = String::from_utf8_lossy(name);
+        if checksum(body, 0) != sum {
+            return Err(broken(format!("the {name} section's checksum is wrong")));
+        }
+        let grid = || {
+            if body.len() != size {
+                return Err(broken(format!(
+                    "the {name} section isn't the size of the grid"
+                )));
+            }
+            Ok(Some(body))
+        };
+        match &*name {
+            "GRBS" => rebus_grid = grid()?,
+            "RTBL" => rebus_table = Some(body),
+            "GEXT" => markup = grid()?,
+            _ => {}
+        }
+    }
+
+    // entries like " 1:HEART;", keyed by the number in GRBS less one
+    let rebus_table = String::from_utf8_lossy(rebus_table.unwrap_or_default());
+    let rebus: Vec<(u8, &str)> = rebus_table
+        .split(';')
+        .filter_map(|entry| {
+            let (key, answer) = entry.split_once(':')?;
+            Some((key.trim().parse().ok()?, answer))
+        })
+        .collect();
+
+    let mut cells: Vec<_> = (0..size)
+        .map(|i| {
+            let letter = solution[i];
+            let kind = match letter {
+                b'.' => CellKind::Block,
+                b':' => CellKind::Void,
+                _ if markup.is_some_and(|markup| markup[i] & CIRCLED != 0) => CellKind::Circled,
+                _ => CellKind::Normal,
+            };
+            let letter = char::from(letter).to_string();
+            let rebus = rebus_grid
+                .map(|grid| grid[i])
+                .filter(|&key| key > 0)
+                .and_then(|key| rebus.iter().find(|&&(k, _)| k == key - 1))
+                .map(|&(_, answer)| answer.to_owned());
+            let (answer, more_answers) = match rebus {
+                _ if scrambled || matches!(kind, CellKind::Block | CellKind::Void) => (None, None),
+                Some(rebus) => (
+                    Some(rebus),
+                    Some(MoreAnswers {
+                        valid: vec![letter],
+                    }),
+                ),
+                None => (Some(letter), None),
+            };
+            Cell {
+                answer,
+                clues: Vec::new(),
+                label: None,
+                more_answers,
+                kind,
+            }
+        })
+        .collect();
+
+    // number the squares that start an answer, going along each row in turn,
+    // and hand out the clues in the same order, across before down
+    let open = |cells: &[Cell], row: usize, column: usize| {
+        row < height
+            && column < width
+            && !matches!(
+                cells[row * width + column].kind,
+                CellKind::Block | CellKind::Void
+            )
+    };
+    let mut texts = strings.clues.iter();
+    let mut clues = Vec::new();
+    let mut number = 0;
+    for i in 0..size {
+        let (row, column) = (i / width, i % width);
+        if !open(&cells, row, column) {
+            continue;
+        }
+        let across =
+            (column == 0 || !open(&cells, row, column - 1)) && open(&cells, row, column + 1);
+        let down = (row == 0 || !open(&cells, row - 1, column)) && open(&cells, row + 1, column);
+        if !across && !down {
+            continue;
+        }
+        number += 1;
+        cells[i].label = Some(number.to_string());
+        for (starts, direction) in [(across, Direction::Across), (down, Direction::Down)] {
+            if !starts {
+                continue;
+            }
+            let squares: Vec<u16> = match direction {
+                Direction::Across => (column..width)
+                    .take_while(|&c| open(&cells, row, c))
+                    .map(|c| (row * width + c) as u16)
+                    .collect(),
+                Direction::Down => (row..height)
+                    .take_while(|&r| open(&cells, r, column))
+                    .map(|r| (r * width + column) as u16)
+                    .collect(),
+            };
+            let text = texts.next().ok_or_else(|| {
+                broken(format!(
+                    "it has {clue_count} clues, but the grid needs more"
+                ))
+            })?;
+            clues.push(Clue {
+                cells: squares,
+                direction,
+                label: number.to_string(),
+                text: vec![ClueText {
+                    formatted: None,
+                    plain: strings.decode(text),
+                }],
+            });
+        }
+    }
+    if texts.next().is_some() {
+        return Err(broken(format!(
+            "it has {clue_count} clues, but the grid only needs {}",
+            clues.len()
+        )));
+    }
+
+    for (index, clue) in clues.iter().enumerate() {
+        for &square in &clue.cells {
+            cells[usize::from(square)].clues.push(index as u16);
+        }
+    }
+    let clue_lists = [Direction::Across, Direction::Down]
+        .into_iter()
+        .map(|name| ClueList {
+            clues: (0..clues.len() as u16)
+                .filter(|&i| clues[usize::from(i)].direction == name)
+                .collect(),
+            name,
+        })
+        .collect();
+
+    let text = |s: &[u8]| Some(strings.decode(s)).filter(|s| !s.is_empty());
+    Ok(Crossword {
+        body: vec![Puzzle {
+            board: String::new(),
+            cells,
+            clue_lists,
+            clues,
+            dimensions: Dimensions { height, width },
+        }],
+        constructors: text(strings.author).into_iter().collect(),
+        copyright: text(strings.copyright),
+        editor: String::new(),
+        id: None,
+        last_updated: None,
+        publication_date: None,
+        title: text(strings.title),
+        extra: serde_json::Map::new(),
+    })
+}
+
+fn broken(message: impl Into<String>) -> Error {
+    Error::Puz(message.into())
+}
+
+/// The text after the grids, as it's stored.
+struct Strings<'a> {
+    title: &'a [u8],
+    author: &'a [u8],
+    copyright: &'a [u8],
+    clues: Vec<&'a [u8]>,
+    notes: &'a [u8],
+    /// The version string from the header, like `1.3`.
+    version: &'a [u8],
+}
+
+impl Strings<'_> {
+    /// Add the text to `sum` the way the checksums in the header do: the
+    /// strings that aren't empty with their terminating NULs, except for the
+    /// clues.
+    fn checksum(&self, mut sum: u16) -> u16 {
+        let mut strings = vec![self.title, self.author, self.copyright];
+        // notes only count from version 1.3 on
+        if self.version >= b"1.3".as_slice() {
+            strings.push(self.notes);
+        }
+        for (i, s) in strings.into_iter().enumerate() {
+            if !s.is_empty() {
+                sum = checksum(&[s, b"\0"].concat(), sum);
+            }
+            if i == 2 {
+                for clue in &self.clues {
+                    sum = checksum(clue, sum);
+                }
+            }
+        }
+        sum
+    }
+
+    /// Text is UTF-8 from version 2.0 on, and Windows-1252 before that.
+    fn decode(&self, s: &[u8]) -> String {
+        if self.version >= b"2.0".as_slice() {
+            return String::from_utf8_lossy(s).into_owned();
+        }
+        s.iter()
+            .map(|&b| CodePage::Wpc1252.char(b).unwrap_or(char::from(b)))
+            .collect()
+    }
+}
+
+/// The checksum used all over the format: rotate right a bit, then add the
+/// next byte.
+fn checksum(data: &[u8], sum: u16) -> u16 {
+    data.iter().fold(sum, |sum, &b| {
+        sum.rotate_right(1).wrapping_add(u16::from(b))
+    })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| broken("it ends too soon"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn word(&mut self) -> Result<u16, Error> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// A NUL terminated string, without the NUL.
+    fn string(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.data[self.pos..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| broken("it ends too soon"))?;
+        let s = self.bytes(len)?;
+        self.pos += 1;
+        Ok(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &[u8] = include_bytes!("../tests/fixtures/rebus.puz");
+
+    #[test]
+    fn numbers_the_grid() {
+        let crossword = parse(FIXTURE).unwrap();
+        assert_eq!(crossword.title.as_deref(), Some("Test Puzzle"));
+        assert_eq!(crossword.constructors, ["Jane Doe"]);
+        assert_eq!(crossword.copyright.as_deref(), Some("© 2024 Jane Doe"));
+
+        let puzzle = &crossword.body[0];
+        let labels: Vec<_> = puzzle
+            .cells
+            .iter()
+            .map(|cell| cell.label.as_deref().unwrap_or("-"))
+            .collect();
+        assert_eq!(
+            labels.join(" "),
+            "1 2 3 4 - 5 - - - 6 7 - - - - 8 - - - - - 9 - - -"
+        );
+        let list = |i: usize| -> Vec<_> {
+            puzzle.clue_lists[i]
+                .clues
+                .iter()
+                .map(|&i| &*puzzle.clues[usize::from(i)].label)
+                .collect()
+        };
+        assert_eq!(list(0), ["1", "5", "7", "8", "9"]);
+        assert_eq!(list(1), ["1", "2", "3", "4", "6"]);
+
+        let clue = &puzzle.clues[5];
+        assert_eq!((clue.direction, &*clue.label), (Direction::Across, "5"));
+        assert_eq!(clue.cells, [5, 6, 7, 8, 9]);
+        assert_eq!(clue.text[0].plain, "Has a “rebus” square");
+        assert_eq!(puzzle.clues[6].cells, [9, 14, 19, 24]);
+        assert_eq!(puzzle.cells[9].clues, [5, 6]);
+    }
+
+    #[test]
+    fn reads_rebus_and_circles() {
+        let crossword = parse(FIXTURE).unwrap();
+        let cells = &crossword.body[0].cells;
+        assert_eq!(cells[6].answer.as_deref(), Some("HEART"));
+        assert_eq!(cells[6].more_answers.as_ref().unwrap().valid, ["H"]);
+        assert_eq!(cells[7].answer.as_deref(), Some("G"));
+        assert_eq!(cells[12].kind, CellKind::Circled);
+        assert_eq!(cells[4].kind, CellKind::Block);
+        assert!(cells[4].answer.is_none());
+    }
+
+    #[test]
+    fn checks_checksums() {
+        // skipped junk before the header
+        let mut data = b"junk".to_vec();
+        data.extend_from_slice(FIXTURE);
+        assert!(parse(&data).is_ok());
+
+        let corrupt = |i: usize| {
+            let mut data = FIXTURE.to_vec();
+            data[i] ^= 1;
+            match parse(&data) {
+                Err(Error::Puz(message)) => message,
+                _ => panic!("byte {i} wasn't checked"),
+            }
+        };
+        // the solution, a clue and the GEXT section
+        assert_eq!(corrupt(0x34), "the file checksum is wrong");
+        assert_eq!(corrupt(0x90), "the file checksum is wrong");
+        assert_eq!(
+            corrupt(FIXTURE.len() - 3),
+            "the GEXT section's checksum is wrong"
+        );
+        assert!(!is_puz(br#"{"body": []}"#));
+    }
+}
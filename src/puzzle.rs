@@ -0,0 +1,289 @@
+//! The v6 crossword JSON served by the NYT.
+
+use clap::ValueEnum;
+use jiff::civil::{date, Date};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// The crosswords the NYT publishes every day, named as in the v6 API.
+#[derive(Clone, Copy, Default, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Variant {
+    #[default]
+    Mini,
+    /// The daily crossword: 15x15, or 21x21 on Sundays. Only subscribers can
+    /// download it, so it needs an NYT-S cookie in the config
+    Daily,
+}
+
+impl Variant {
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Mini => "mini",
+            Variant::Daily => "daily",
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Variant::Mini => "The NYT Mini Crossword",
+            Variant::Daily => "The NYT Crossword",
+        }
+    }
+
+    /// The earliest puzzle that can be downloaded. The daily goes back
+    /// further, but the NYT's archive starts here.
+    pub fn first(self) -> Date {
+        match self {
+            Variant::Mini => date(2014, 8, 21),
+            Variant::Daily => date(1993, 11, 21),
+        }
+    }
+}
+
+/// Any of the NYT's crosswords, as served by the v6 API.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Crossword {
+    pub body: Vec<Puzzle>,
+    pub constructors: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copyright: Option<String>,
+    pub editor: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_updated: Option<String>,
+    /// Unknown for a `.puz`, which doesn't say.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publication_date: Option<Date>,
+    /// The theme's title, which Sunday puzzles usually have.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Anything else the NYT sends, like `subcategory`, kept so that a
+    /// puzzle saves back out the same as it came in.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Crossword {
+    /// Which crossword this is, going by its size, for puzzles that didn't
+    /// come from the NYT's API: anything as wide as a daily is one.
+    pub fn variant(&self) -> Variant {
+        match self.body.first() {
+            Some(puzzle) if puzzle.dimensions.width >= 15 => Variant::Daily,
+            _ => Variant::Mini,
+        }
+    }
+
+    /// The name to head a receipt with: the NYT's for one of its own
+    /// crosswords, or the title of a `.puz`, which is someone else's.
+    pub fn heading(&self) -> &str {
+        match self.body.first() {
+            // only a .puz comes without a board
+            Some(puzzle) if puzzle.board.is_empty() => self.title.as_deref().unwrap_or_default(),
+            _ => self.variant().title(),
+        }
+    }
+
+    /// Make sure the parts a receipt is made from are all there, since JSON
+    /// in the right shape can still be missing some of them.
+    pub fn check(&self) -> Result<(), Error> {
+        let invalid = |message: String| Err(Error::Json(serde::de::Error::custom(message)));
+        let Some(puzzle) = self.body.first() else {
+            return invalid("there's no puzzle in the body".to_owned());
+        };
+        if puzzle.dimensions.width == 0 {
+            return invalid("the grid is 0 squares wide".to_owned());
+        }
+        for list in &puzzle.clue_lists {
+            for &clue_num in &list.clues {
+                match puzzle.clues.get(usize::from(clue_num)) {
+                    None => {
+                        return invalid(format!(
+                            "the {:?} clues include clue {clue_num}, which doesn't exist",
+                            list.name
+                        ))
+                    }
+                    Some(clue) if clue.text.is_empty() => {
+                        return invalid(format!("clue {} has no text", clue.label))
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The name from before other crosswords were supported.
+pub type MiniCrossword = Crossword;
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Puzzle {
+    /// The grid as an SVG image. Empty for a `.puz`, which doesn't have one.
+    pub board: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cells: Vec<Cell>,
+    pub clue_lists: Vec<ClueList>,
+    pub clues: Vec<Clue>,
+    pub dimensions: Dimensions,
+}
+
+/// A square of the grid; blocks are sent as `{}` and have no answer.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cell {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub answer: Option<String>,
+    /// Indices into [`Puzzle::clues`] of the clues that cross this square.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clues: Vec<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Other answers accepted for a rebus square, like just the first letter.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub more_answers: Option<MoreAnswers>,
+    #[serde(rename = "type", default, skip_serializing_if = "CellKind::is_block")]
+    pub kind: CellKind,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct MoreAnswers {
+    pub valid: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Debug)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum CellKind {
+    #[default]
+    Block,
+    Normal,
+    Circled,
+    Shaded,
+    /// Not part of the puzzle at all, for grids that aren't rectangular
+    Void,
+}
+
+impl CellKind {
+    fn is_block(&self) -> bool {
+        *self == CellKind::Block
+    }
+}
+
+impl TryFrom<u8> for CellKind {
+    type Error = String;
+
+    fn try_from(n: u8) -> Result<Self, Self::Error> {
+        match n {
+            0 => Ok(CellKind::Block),
+            1 => Ok(CellKind::Normal),
+            2 => Ok(CellKind::Circled),
+            3 => Ok(CellKind::Shaded),
+            4 => Ok(CellKind::Void),
+            _ => Err(format!("unknown cell type {n}")),
+        }
+    }
+}
+
+impl From<CellKind> for u8 {
+    fn from(kind: CellKind) -> u8 {
+        match kind {
+            CellKind::Block => 0,
+            CellKind::Normal => 1,
+            CellKind::Circled => 2,
+            CellKind::Shaded => 3,
+            CellKind::Void => 4,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Dimensions {
+    pub height: usize,
+    pub width: usize,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ClueList {
+    pub clues: Vec<u16>,
+    pub name: Direction,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Across,
+    Down,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Clue {
+    /// Indices into [`Puzzle::cells`] of the squares in the answer.
+    pub cells: Vec<u16>,
+    pub direction: Direction,
+    pub label: String,
+    pub text: Vec<ClueText>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ClueText {
+    /// The same text with HTML markup, only sent when the clue has some.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formatted: Option<String>,
+    pub plain: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &[&str] = &[
+        include_str!("../tests/fixtures/mini-2024-03-01.json"),
+        include_str!("../tests/fixtures/mini-2024-03-02.json"),
+        // made up, with the same layout as a real 15x15
+        include_str!("../tests/fixtures/daily-2024-03-04.json"),
+    ];
+
+    #[test]
+    fn round_trip() {
+        for fixture in FIXTURES {
+            let json: serde_json::Value = serde_json::from_str(fixture).unwrap();
+            let mini: Crossword = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&mini).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn clues_map_to_cells() {
+        for fixture in FIXTURES {
+            let mini: Crossword = serde_json::from_str(fixture).unwrap();
+            let puzzle = &mini.body[0];
+            for (i, clue) in puzzle.clues.iter().enumerate() {
+                let first = &puzzle.cells[usize::from(clue.cells[0])];
+                assert_eq!(first.label.as_ref(), Some(&clue.label));
+                for &cell in &clue.cells {
+                    let cell = &puzzle.cells[usize::from(cell)];
+                    assert!(cell.answer.is_some());
+                    assert!(cell.clues.contains(&(i as u16)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cell_kinds_and_rebus() {
+        let mini: Crossword = serde_json::from_str(FIXTURES[1]).unwrap();
+        let cells = &mini.body[0].cells;
+        assert_eq!(cells[0].kind, CellKind::Block);
+        assert_eq!(cells[1].kind, CellKind::Circled);
+        assert_eq!(cells[6].kind, CellKind::Shaded);
+        let rebus = cells
+            .iter()
+            .find(|cell| cell.more_answers.is_some())
+            .unwrap();
+        assert_eq!(rebus.answer.as_deref(), Some("HEART"));
+        assert_eq!(rebus.more_answers.as_ref().unwrap().valid, ["H"]);
+    }
+}
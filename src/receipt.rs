@@ -0,0 +1,473 @@
+//! Laying out a puzzle as a receipt, for any ESC/POS printer.
+
+use escpos::driver::Driver;
+use escpos::printer::Printer;
+use escpos::printer_options::PrinterOptions;
+use escpos::utils::{BitImageOption, BitImageSize, JustifyMode, Protocol};
+use resvg::{tiny_skia, usvg};
+use unicode_width::UnicodeWidthStr;
+
+use crate::charset::{Charset, CodePage};
+use crate::config::{Cut, GridRenderer, Layout, Section};
+use crate::error::Error;
+use crate::fonts;
+use crate::grid;
+use crate::landscape;
+use crate::puzzle::{ClueList, Crossword, Puzzle};
+use crate::styled::{self, Line, Style, Styled};
+
+/// Blank columns between the Across and Down clues when they're side by side.
+const COLUMN_GAP: usize = 2;
+
+/// The least room for clue text next to its label in a column.
+const MIN_CLUE_WIDTH: usize = 8;
+
+/// A puzzle and how to print it. Start from [`Receipt::new`] for the defaults
+/// and change what you need:
+///
+/// ```no_run
+/// # use miniprint::{config::Cut, Crossword, Receipt};
+/// # fn main() -> Result<(), miniprint::Error> {
+/// let json = miniprint::fetch::download(None, &Default::default(), |_, _| {})?;
+/// let crossword: Crossword = serde_json::from_str(&json)?;
+/// let driver = escpos::driver::FileDriver::open(std::path::Path::new("/dev/usb/lp0"))?;
+/// Receipt::new(&crossword).cut(Cut::Partial).print(driver)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Receipt<'a> {
+    crossword: &'a Crossword,
+    layout: Layout,
+    cut: Cut,
+    code_pages: Vec<CodePage>,
+    raster_glyphs: bool,
+}
+
+impl<'a> Receipt<'a> {
+    pub fn new(crossword: &'a Crossword) -> Self {
+        Receipt {
+            crossword,
+            layout: Layout::default(),
+            cut: Cut::default(),
+            code_pages: vec![CodePage::default()],
+            raster_glyphs: false,
+        }
+    }
+
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// What to do with the paper at the end.
+    pub fn cut(mut self, cut: Cut) -> Self {
+        self.cut = cut;
+        self
+    }
+
+    /// The code pages the printer has, best first.
+    pub fn code_pages(mut self, code_pages: Vec<CodePage>) -> Self {
+        self.code_pages = code_pages;
+        self
+    }
+
+    /// Draw characters the code pages don't have as user-defined characters.
+    pub fn raster_glyphs(mut self, raster_glyphs: bool) -> Self {
+        self.raster_glyphs = raster_glyphs;
+        self
+    }
+
+    /// Print the receipt on a new [`Printer`] for `driver`, set up for the
+    /// paper width.
+    pub fn print<D: Driver>(&self, driver: D) -> Result<(), Error> {
+        let mut printer = Printer::new(
+            driver,
+            Protocol::default(),
+            Some(PrinterOptions::new(
+                None,
+                None,
+                self.layout.chars_per_line(),
+            )),
+        );
+        self.write(&mut printer)
+    }
+
+    /// Send the receipt to `printer`, ending with the cut. Nothing is sent if
+    /// the puzzle doesn't [check](Crossword::check) out.
+    pub fn write<D: Driver>(&self, printer: &mut Printer<D>) -> Result<(), Error> {
+        let crossword = self.crossword;
+        crossword.check()?;
+        let layout = &self.layout;
+        let chars_per_line = layout.chars_per_line();
+        let wrap_opts = || textwrap::Options::new(chars_per_line.into());
+
+        let puzzle = &crossword.body[0];
+
+        let charset = Charset::new(
+            &self.code_pages,
+            self.raster_glyphs.then_some(layout.font_width),
+            &receipt_text(crossword, layout.header(crossword)),
+        );
+        charset.select(printer)?;
+
+        // landscape draws everything but the solution as a single image
+        let portrait = !layout.landscape;
+        if layout.landscape {
+            print_image(printer, &landscape::render(crossword, layout)?)?;
+            printer.feed()?;
+        }
+
+        if portrait && layout.shows(Section::Header) {
+            let header = layout.header(crossword);
+            if !header.is_empty() {
+                charset.writeln(printer, header)?;
+            }
+            if let Some(date) = crossword.publication_date {
+                charset.writeln(printer, &date.strftime("%A, %B %-d, %Y").to_string())?;
+            }
+            // a .puz is headed with its title already
+            if let Some(title) = crossword.title.as_deref().filter(|&t| t != header) {
+                charset.writeln(printer, &charset.prepare(title))?;
+            }
+            printer.feed()?;
+        }
+
+        if portrait && layout.shows(Section::Grid) {
+            let width = u32::from(layout.paper.image_width());
+            // a .puz has no SVG board to draw instead
+            let native = match layout.grid {
+                GridRenderer::Svg if !puzzle.board.is_empty() => None,
+                _ => {
+                    // the squares that fill the paper, scaled up, but never
+                    // smaller than min_cell
+                    let columns = puzzle.dimensions.width.max(1) as u32;
+                    let fit = width.saturating_sub(layout.line_width) / columns;
+                    let cell = ((fit as f32 * layout.grid_scale) as u32).max(layout.min_cell);
+                    grid::render_cells(puzzle, width, cell, layout.line_width)
+                }
+            };
+            let image = match native {
+                Some(image) => image,
+                None => {
+                    let board_width = f32::from(layout.paper.image_width()) * layout.grid_scale;
+                    render_board(&puzzle.board, board_width.round(), layout.dpi)?
+                }
+            };
+            let strips = grid::tile(&image, width).ok_or(Error::EmptyCanvas {
+                width: width as f32,
+                height: image.height() as f32,
+            })?;
+            for strip in &strips {
+                print_image(printer, strip)?;
+                printer.feed()?.feed()?;
+            }
+        }
+
+        let write_wrapped = |printer: &mut Printer<_>, text: &str, opts: textwrap::Options<'_>| {
+            let text = charset.prepare(text);
+            textwrap::wrap(&text, opts)
+                .into_iter()
+                .try_for_each(|line| charset.writeln(printer, &line))
+        };
+
+        if portrait && layout.shows(Section::Clues) {
+            let lists = puzzle
+                .clue_lists
+                .iter()
+                .map(|clues| {
+                    let mut entries = clue_entries(crossword, clues, layout);
+                    for entry in &mut entries {
+                        entry.text = entry.text.map(|s| charset.prepare(s));
+                    }
+                    (format!("{:?}:", clues.name), entries)
+                })
+                .collect::<Vec<_>>();
+            let offset = (usize::from(chars_per_line) + COLUMN_GAP) / 2;
+            let width = offset.saturating_sub(COLUMN_GAP);
+            // a column needs room for its heading, and for some of a clue
+            // after the widest label
+            let fits = |(heading, entries): &(String, Vec<ClueEntry>)| {
+                let label = entries.iter().map(|e| e.label.width()).max().unwrap_or(0);
+                heading.width() <= width && label + MIN_CLUE_WIDTH <= width
+            };
+            match &lists[..] {
+                // too narrow a line for two columns prints them one after the other
+                [(across, across_entries), (down, down_entries)]
+                    if layout.two_column_clues && lists.iter().all(fits) =>
+                {
+                    styled::write_columns(
+                        printer,
+                        &charset,
+                        [
+                            &clue_column(across, across_entries, width),
+                            &clue_column(down, down_entries, width),
+                        ],
+                        offset,
+                    )?;
+                    printer.feed()?;
+                }
+                _ => {
+                    for (heading, entries) in &lists {
+                        charset.writeln(printer, heading)?;
+                        for entry in entries {
+                            styled::write_wrapped(
+                                printer,
+                                &charset,
+                                &entry.text,
+                                chars_per_line.into(),
+                                &entry.label,
+                                &entry.indent,
+                            )?;
+                        }
+                        printer.feed()?;
+                    }
+                }
+            }
+        }
+
+        if portrait && layout.shows(Section::Byline) {
+            // a .puz only has an author, if that
+            if !crossword.constructors.is_empty() {
+                write_wrapped(
+                    printer,
+                    &format_list(&crossword.constructors),
+                    wrap_opts().initial_indent("By ").subsequent_indent("   "),
+                )?;
+            }
+            if !crossword.editor.is_empty() {
+                charset.write(printer, "Edited by ")?;
+                charset.writeln(printer, &crossword.editor)?;
+            }
+        }
+
+        if layout.shows(Section::Solution) {
+            printer.feed()?;
+            print_solution(
+                printer,
+                &charset,
+                puzzle,
+                chars_per_line,
+                layout.upside_down_solution,
+            )?;
+        }
+        charset.deselect(printer)?;
+
+        match self.cut {
+            Cut::Full => printer.print_cut()?,
+            Cut::Partial => printer.partial_cut()?.print()?,
+            Cut::None => printer.feeds(3)?.print()?,
+        };
+
+        Ok(())
+    }
+}
+
+/// Send `image` in bands of up to this many rows. escpos can't work out the
+/// size of a GS v 0 image much over 32,000 rows tall, which a landscape
+/// receipt in a big font can be.
+const BAND_HEIGHT: u32 = 2048;
+
+fn print_image<D: Driver>(
+    printer: &mut Printer<D>,
+    image: &tiny_skia::Pixmap,
+) -> Result<(), Error> {
+    let width = image.width();
+    for top in (0..image.height()).step_by(BAND_HEIGHT as usize) {
+        let height = BAND_HEIGHT.min(image.height() - top);
+        let band = tiny_skia::IntRect::from_xywh(0, top as i32, width, height)
+            .and_then(|rect| image.clone_rect(rect))
+            .ok_or_else(|| Error::Image(format!("couldn't cut a {width}x{height} band")))?;
+        let image_opts = BitImageOption::new(Some(width), None, BitImageSize::Normal)?;
+        let png = band.encode_png().map_err(|e| Error::Image(e.to_string()))?;
+        printer.bit_image_from_bytes_option(&png, image_opts)?;
+    }
+    Ok(())
+}
+
+/// A clue ready to be wrapped.
+pub(crate) struct ClueEntry {
+    /// The clue's number and whatever follows it, before the first line.
+    pub label: String,
+    /// Blanks as wide as the label, before the lines after that.
+    pub indent: String,
+    pub text: Styled,
+}
+
+/// The clues in `clues` with their labels. Compact clues line their numbers up
+/// on the right of a gutter that fits the longest, instead of following each
+/// with a colon.
+pub(crate) fn clue_entries(
+    crossword: &Crossword,
+    clues: &ClueList,
+    layout: &Layout,
+) -> Vec<ClueEntry> {
+    let puzzle = &crossword.body[0];
+    let compact = layout.compact_clues(crossword);
+    let gutter = clues
+        .clues
+        .iter()
+        .map(|&clue_num| puzzle.clues[clue_num as usize].label.width())
+        .max()
+        .unwrap_or(0);
+    clues
+        .clues
+        .iter()
+        .map(|&clue_num| {
+            let clue = &puzzle.clues[clue_num as usize];
+            let label = if compact {
+                let pad = " ".repeat(gutter - clue.label.width());
+                format!("{pad}{} ", clue.label)
+            } else {
+                format!("{}: ", clue.label)
+            };
+            let text = match &clue.text[0].formatted {
+                Some(html) => Styled::parse(html, layout.italics),
+                None => Styled::plain(&clue.text[0].plain),
+            };
+            ClueEntry {
+                indent: " ".repeat(label.width()),
+                label,
+                text,
+            }
+        })
+        .collect()
+}
+
+/// A heading and its clues wrapped to `width` columns, for printing next to
+/// another.
+fn clue_column<'a>(heading: &'a str, entries: &'a [ClueEntry], width: usize) -> Vec<Line<'a>> {
+    let mut lines = vec![vec![(Style::default(), heading)]];
+    for entry in entries {
+        lines.extend(styled::wrap(
+            &entry.text,
+            width,
+            &entry.label,
+            &entry.indent,
+        ));
+    }
+    lines
+}
+
+/// Print the answers as a block of letters, with `#` for the black squares.
+///
+/// Upside down, the lines are sent bottom row first so that the block reads
+/// correctly once the receipt is turned around.
+fn print_solution<D: Driver>(
+    printer: &mut Printer<D>,
+    charset: &Charset,
+    puzzle: &Puzzle,
+    chars_per_line: u8,
+    upside_down: bool,
+) -> Result<(), Error> {
+    // rebus squares hold more than one letter, so every cell gets the same width
+    let cell_width = puzzle
+        .cells
+        .iter()
+        .filter_map(|cell| cell.answer.as_deref())
+        .map(|answer| answer.width())
+        .max()
+        .unwrap_or(1);
+    let width = puzzle.dimensions.width;
+    let spaced = width * (cell_width + 1) - 1 <= chars_per_line.into();
+    let separator = if spaced { " " } else { "" };
+
+    let mut lines = vec!["Solution".to_owned()];
+    for row in puzzle.cells.chunks(width) {
+        let row = row
+            .iter()
+            .map(|cell| match &cell.answer {
+                Some(answer) => format!("{answer:^cell_width$}"),
+                None => "#".repeat(cell_width),
+            })
+            .collect::<Vec<_>>();
+        lines.push(row.join(separator));
+    }
+
+    printer.justify(JustifyMode::CENTER)?;
+    if upside_down {
+        printer.upside_down(true)?;
+        lines.reverse();
+    }
+    for line in &lines {
+        charset.writeln(printer, line)?;
+    }
+    if upside_down {
+        printer.upside_down(false)?;
+    }
+    printer.justify(JustifyMode::LEFT)?;
+    Ok(())
+}
+
+/// Everything from the puzzle that can end up on the receipt, to pick a code
+/// page for.
+fn receipt_text(crossword: &Crossword, header: &str) -> String {
+    let mut text = vec![header, &crossword.editor];
+    text.extend(crossword.title.as_deref());
+    text.extend(crossword.constructors.iter().map(String::as_str));
+    for puzzle in &crossword.body {
+        for clue in &puzzle.clues {
+            for part in &clue.text {
+                text.push(&part.plain);
+                text.extend(part.formatted.as_deref());
+            }
+        }
+        text.extend(
+            puzzle
+                .cells
+                .iter()
+                .filter_map(|cell| cell.answer.as_deref()),
+        );
+    }
+    text.join("\n")
+}
+
+/// Rasterize the puzzle's SVG board `target_width` dots wide.
+pub(crate) fn render_board(
+    board: &str,
+    target_width: f32,
+    dpi: f32,
+) -> Result<tiny_skia::Pixmap, Error> {
+    let mut opt = usvg::Options {
+        dpi,
+        shape_rendering: usvg::ShapeRendering::CrispEdges,
+        ..Default::default()
+    };
+    fonts::load(&mut opt);
+    let svg = usvg::Tree::from_str(board, &opt)?;
+    let size = svg.size();
+
+    let scale = target_width / svg.size().width();
+    // canvas width should be the full target width, but the render transform is rounded
+    // to an even-ish number so that lines don't get lost rendering to the low resolution
+    let scale = (scale * 100.0).round() / 100.0;
+    let (width, height) = (target_width, size.height() * scale);
+    let empty = || Error::EmptyCanvas { width, height };
+    let canvas_size = usvg::Size::from_wh(width, height)
+        .ok_or_else(empty)?
+        .to_int_size();
+    let trans = usvg::Transform::from_scale(scale, scale);
+
+    let mut buf =
+        tiny_skia::Pixmap::new(canvas_size.width(), canvas_size.height()).ok_or_else(empty)?;
+    resvg::render(&svg, trans, &mut buf.as_mut());
+    Ok(buf)
+}
+
+pub(crate) fn format_list(s: &[String]) -> String {
+    match s {
+        [x] => x.clone(),
+        [x, y] => [x, " and ", y].concat(),
+        xs => {
+            let mut out = String::new();
+            for (i, x) in xs.iter().enumerate() {
+                if i == xs.len() - 1 {
+                    out.push_str(", and ");
+                } else if i != 0 {
+                    out.push_str(", ");
+                }
+                out.push_str(x);
+            }
+            out
+        }
+    }
+}
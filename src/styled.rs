@@ -0,0 +1,396 @@
+//! Clue text with the handful of HTML tags the NYT uses, mapped onto the
+//! styles a receipt printer actually has.
+//!
+//! The text is wrapped as plain characters and the style codes are only added
+//! when each line is written, so they never count towards the line width.
+
+use std::ops::Range;
+
+use escpos::driver::Driver;
+use escpos::printer::Printer;
+use escpos::utils::UnderlineMode;
+use textwrap::core::{break_words, Word};
+use textwrap::word_splitters::{split_words, WordSplitter};
+use textwrap::{WordSeparator, WrapAlgorithm};
+use unicode_width::UnicodeWidthStr;
+
+use crate::charset::Charset;
+use crate::config::Italics;
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Style {
+    pub bold: bool,
+    pub underline: bool,
+    pub double_strike: bool,
+}
+
+/// Text along with the style of each run of it.
+pub struct Styled {
+    text: String,
+    /// Start offsets into `text` and the style from there on, in order.
+    runs: Vec<(usize, Style)>,
+}
+
+impl Styled {
+    pub fn plain(text: &str) -> Styled {
+        Styled {
+            text: text.to_owned(),
+            runs: vec![(0, Style::default())],
+        }
+    }
+
+    /// Parse the `formatted` text of a clue. Bold, underline and italic tags
+    /// become printer styles, `sup` is marked with a `^` and `br` becomes a
+    /// space. Any other tag is dropped but its contents kept, so subscripts
+    /// just print inline, as in H2O.
+    pub fn parse(html: &str, italics: Italics) -> Styled {
+        let mut styled = Styled {
+            text: String::new(),
+            runs: vec![(0, Style::default())],
+        };
+        let (mut bold, mut underline, mut italic) = (0_u32, 0_u32, 0_u32);
+        let mut rest = html;
+
+        while let Some(c) = rest.chars().next() {
+            if c == '<' {
+                if let Some(end) = rest.find('>') {
+                    let tag = &rest[1..end];
+                    rest = &rest[end + 1..];
+                    let closing = tag.starts_with('/');
+                    let name = tag
+                        .trim_start_matches('/')
+                        .split(|c: char| c.is_whitespace() || c == '/')
+                        .next()
+                        .unwrap_or_default()
+                        .to_ascii_lowercase();
+                    let count = match name.as_str() {
+                        "b" | "strong" => &mut bold,
+                        "u" => &mut underline,
+                        "i" | "em" | "cite" => {
+                            if let Italics::Marker = italics {
+                                styled.push("_");
+                            }
+                            &mut italic
+                        }
+                        // there's no raised text either, so mark it like a
+                        // calculator would: x^2
+                        "sup" if !closing => {
+                            styled.push("^");
+                            continue;
+                        }
+                        "br" => {
+                            styled.push(" ");
+                            continue;
+                        }
+                        _ => continue,
+                    };
+                    *count = if closing {
+                        count.saturating_sub(1)
+                    } else {
+                        *count + 1
+                    };
+                    styled.set_style(Style {
+                        bold: bold > 0,
+                        underline: underline > 0
+                            || (italic > 0 && matches!(italics, Italics::Underline)),
+                        double_strike: italic > 0 && matches!(italics, Italics::DoubleStrike),
+                    });
+                    continue;
+                }
+            } else if c == '&' {
+                if let Some((decoded, len)) = entity(rest) {
+                    styled.push(decoded.encode_utf8(&mut [0; 4]));
+                    rest = &rest[len..];
+                    continue;
+                }
+            }
+            styled.push(c.encode_utf8(&mut [0; 4]));
+            rest = &rest[c.len_utf8()..];
+        }
+        styled
+    }
+
+    /// Append text in the current style, collapsing whitespace like a browser would.
+    fn push(&mut self, s: &str) {
+        for c in s.chars() {
+            if c.is_whitespace() {
+                if !self.text.is_empty() && !self.text.ends_with(' ') {
+                    self.text.push(' ');
+                }
+            } else {
+                self.text.push(c);
+            }
+        }
+    }
+
+    fn set_style(&mut self, style: Style) {
+        let start = self.text.len();
+        match self.runs.last_mut() {
+            Some((_, last)) if *last == style => {}
+            Some((last_start, last)) if *last_start == start => *last = style,
+            _ => self.runs.push((start, style)),
+        }
+    }
+
+    /// Replace the text of each run with `f` of it, keeping its style.
+    pub fn map(&self, f: impl Fn(&str) -> String) -> Styled {
+        let mut mapped = Styled::plain("");
+        for (style, s) in self.runs_in(0..self.text.len()) {
+            mapped.set_style(style);
+            mapped.text.push_str(&f(s));
+        }
+        mapped
+    }
+
+    /// Wrap the text to `widths` (the first line's width, then the rest) and
+    /// split each line up into runs of the same style.
+    pub fn lines(&self, widths: [usize; 2]) -> Vec<Vec<(Style, &str)>> {
+        let text = self.text.trim_end();
+        let words = WordSeparator::new().find_words(text);
+        let words = split_words(words, &WordSplitter::HyphenSplitter);
+        let words = break_words(words, widths[0].min(widths[1]).max(1));
+        let lines = WrapAlgorithm::new().wrap(&words, &widths);
+
+        lines
+            .into_iter()
+            .map(|words| {
+                let (Some(first), Some(last)) = (words.first(), words.last()) else {
+                    return Vec::new();
+                };
+                let range = self.offset(first)..self.offset(last) + last.word.len();
+                let mut line = self.runs_in(range);
+                if !last.penalty.is_empty() {
+                    let style = line.last().map(|(style, _)| *style).unwrap_or_default();
+                    line.push((style, last.penalty));
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// Where a word split off of `text` starts in it.
+    fn offset(&self, word: &Word<'_>) -> usize {
+        word.word.as_ptr() as usize - self.text.as_ptr() as usize
+    }
+
+    fn runs_in(&self, range: Range<usize>) -> Vec<(Style, &str)> {
+        let ends = self.runs.iter().skip(1).map(|&(start, _)| start);
+        self.runs
+            .iter()
+            .zip(ends.chain([self.text.len()]))
+            .filter_map(|(&(start, style), end)| {
+                let (start, end) = (start.max(range.start), end.min(range.end));
+                (start < end).then(|| (style, &self.text[start..end]))
+            })
+            .collect()
+    }
+}
+
+/// Decode the character reference at the start of `s`, returning the
+/// character and how long the reference was.
+fn entity(s: &str) -> Option<(char, usize)> {
+    let end = s.get(..12).unwrap_or(s).find(';')?;
+    let name = &s[1..end];
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "hellip" => '…',
+        _ => {
+            let code = match name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => name.strip_prefix('#')?.parse().ok()?,
+            };
+            char::from_u32(code)?
+        }
+    };
+    Some((c, end + 1))
+}
+
+/// A line of wrapped text, indent and all, in runs of the same style.
+pub type Line<'a> = Vec<(Style, &'a str)>;
+
+/// Word wrap `text` to `width` columns, indenting the first line with
+/// `initial_indent` and the others with `subsequent_indent`.
+pub fn wrap<'a>(
+    text: &'a Styled,
+    width: usize,
+    initial_indent: &'a str,
+    subsequent_indent: &'a str,
+) -> Vec<Line<'a>> {
+    let widths = [
+        width.saturating_sub(initial_indent.width()),
+        width.saturating_sub(subsequent_indent.width()),
+    ];
+    text.lines(widths)
+        .into_iter()
+        .enumerate()
+        .map(|(i, runs)| {
+            let indent = if i == 0 {
+                initial_indent
+            } else {
+                subsequent_indent
+            };
+            let mut line = vec![(Style::default(), indent)];
+            line.extend(runs);
+            line
+        })
+        .collect()
+}
+
+/// Word wrap `text`, which should already be [prepared](Charset::prepare),
+/// to `width` columns and print it, indenting the first
+/// line with `initial_indent` and the others with `subsequent_indent`.
+pub fn write_wrapped<D: Driver>(
+    printer: &mut Printer<D>,
+    charset: &Charset,
+    text: &Styled,
+    width: usize,
+    initial_indent: &str,
+    subsequent_indent: &str,
+) -> escpos::errors::Result<()> {
+    for line in wrap(text, width, initial_indent, subsequent_indent) {
+        write_line(printer, charset, &line)?;
+        printer.feed()?;
+    }
+    Ok(())
+}
+
+/// Print two columns of [prepared](Charset::prepare) lines next to each
+/// other, a line of each at a time, with the right one starting `offset`
+/// columns in. The shorter column is padded out with blank lines.
+pub fn write_columns<D: Driver>(
+    printer: &mut Printer<D>,
+    charset: &Charset,
+    [left, right]: [&[Line<'_>]; 2],
+    offset: usize,
+) -> escpos::errors::Result<()> {
+    for i in 0..left.len().max(right.len()) {
+        let left = left.get(i).map(Vec::as_slice).unwrap_or_default();
+        let right = right.get(i).map(Vec::as_slice).unwrap_or_default();
+        write_line(printer, charset, left)?;
+        if !right.is_empty() {
+            let used: usize = left.iter().map(|(_, s)| s.width()).sum();
+            charset.write(printer, &" ".repeat(offset.saturating_sub(used)))?;
+            write_line(printer, charset, right)?;
+        }
+        printer.feed()?;
+    }
+    Ok(())
+}
+
+/// Print the runs of a line without ending it.
+fn write_line<D: Driver>(
+    printer: &mut Printer<D>,
+    charset: &Charset,
+    line: &[(Style, &str)],
+) -> escpos::errors::Result<()> {
+    let mut current = Style::default();
+    for &(style, s) in line {
+        set_style(printer, current, style)?;
+        current = style;
+        charset.write(printer, s)?;
+    }
+    // styles stay on until they're turned off, so leave every line plain
+    set_style(printer, current, Style::default())
+}
+
+/// Send the commands to switch from one style to another.
+fn set_style<D: Driver>(
+    printer: &mut Printer<D>,
+    from: Style,
+    to: Style,
+) -> escpos::errors::Result<()> {
+    if from.bold != to.bold {
+        printer.bold(to.bold)?;
+    }
+    if from.underline != to.underline {
+        printer.underline(if to.underline {
+            UnderlineMode::Single
+        } else {
+            UnderlineMode::None
+        })?;
+    }
+    if from.double_strike != to.double_strike {
+        printer.double_strike(to.double_strike)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOLD: Style = Style {
+        bold: true,
+        underline: false,
+        double_strike: false,
+    };
+    const UNDERLINE: Style = Style {
+        bold: false,
+        underline: true,
+        double_strike: false,
+    };
+    const PLAIN: Style = Style {
+        bold: false,
+        underline: false,
+        double_strike: false,
+    };
+
+    #[test]
+    fn parses_tags_and_entities() {
+        let styled = Styled::parse(
+            "<b>Cornell</b> or <i>Harvard</i>, e.g. &mdash; x<sup>2</sup> &amp;&#233;<br/>&bogus;",
+            Italics::Underline,
+        );
+        assert_eq!(styled.text, "Cornell or Harvard, e.g. — x^2 &é &bogus;");
+        assert_eq!(
+            styled.lines([80, 80]),
+            [vec![
+                (BOLD, "Cornell"),
+                (PLAIN, " or "),
+                (UNDERLINE, "Harvard"),
+                (PLAIN, ", e.g. — x^2 &é &bogus;"),
+            ]]
+        );
+
+        let styled = Styled::parse("<em>Jeopardy!</em> host", Italics::Marker);
+        assert_eq!(styled.text, "_Jeopardy!_ host");
+        assert_eq!(styled.lines([80, 80]), [vec![(PLAIN, "_Jeopardy!_ host")]]);
+    }
+
+    #[test]
+    fn wraps_without_counting_styles() {
+        let styled = Styled::parse("<b>one two</b> three <u>four-five</u>", Italics::Underline);
+        assert_eq!(
+            styled.lines([9, 12]),
+            [
+                vec![(BOLD, "one two")],
+                vec![(PLAIN, "three "), (UNDERLINE, "four-")],
+                vec![(UNDERLINE, "five")],
+            ]
+        );
+    }
+
+    #[test]
+    fn breaks_long_words() {
+        let styled = Styled::plain("abcdefgh ij");
+        assert_eq!(
+            styled.lines([4, 4]),
+            [
+                vec![(PLAIN, "abcd")],
+                vec![(PLAIN, "efgh")],
+                vec![(PLAIN, "ij")]
+            ]
+        );
+    }
+}
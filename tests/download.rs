@@ -0,0 +1,223 @@
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+
+const FIXTURE: &str = include_str!("fixtures/mini-2024-03-01.json");
+
+/// Serve one response per connection from `responses`, as `(status, body)`.
+/// Returns the server's address and a channel that gets each request's head.
+fn stub_server(responses: Vec<(u16, &'static str)>) -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for (status, body) in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            while !head.ends_with("\r\n\r\n") && reader.read_line(&mut head).unwrap() > 0 {}
+            tx.send(head).unwrap();
+            let response = format!(
+                "HTTP/1.1 {status} Whatever\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+        }
+    });
+    (addr, rx)
+}
+
+/// A fresh directory for the test's config and cache.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("miniprint-{}-{name}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Run `miniprint fetch` with `config` as its config file.
+fn fetch(dir: &Path, config: &str) -> Command {
+    let path = dir.join("config.toml");
+    fs::write(&path, config).unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_miniprint"));
+    command
+        .env("XDG_CACHE_HOME", dir.join("cache"))
+        .env_remove("HTTP_PROXY")
+        .env_remove("http_proxy")
+        .env_remove("ALL_PROXY")
+        .env_remove("all_proxy")
+        .args(["fetch", "--config"])
+        .arg(path);
+    command
+}
+
+#[test]
+fn downloads_from_configured_source() {
+    let (addr, requests) = stub_server(vec![(200, FIXTURE)]);
+    let dir = temp_dir("source");
+    let output = fetch(
+        &dir,
+        &format!(
+            "[download]\n\
+             url = \"http://{addr}/mirror/\"\n\
+             nyt-s = \"s3cret\"\n\
+             headers = {{ \"User-Agent\" = \"office-printer\", \"X-Team\" = \"puzzles\" }}\n"
+        ),
+    )
+    .args(["--date", "2024-03-01"])
+    .output()
+    .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(output.stdout, FIXTURE.as_bytes());
+
+    let head = requests.recv().unwrap();
+    assert!(
+        head.starts_with("GET /mirror/mini/2024-03-01.json HTTP/1.1\r\n"),
+        "{head}"
+    );
+    for header in [
+        "User-Agent: office-printer\r\n",
+        "X-Team: puzzles\r\n",
+        "Cookie: NYT-S=s3cret\r\n",
+    ] {
+        assert!(head.contains(header), "{head}");
+    }
+    assert!(!head.contains("miniprinter"), "{head}");
+}
+
+#[test]
+fn goes_through_http_proxy() {
+    let (addr, requests) = stub_server(vec![(200, FIXTURE)]);
+    let dir = temp_dir("proxy");
+    let output = fetch(&dir, "[download]\nurl = \"http://mirror.invalid/svc\"\n")
+        .env("http_proxy", format!("http://{addr}"))
+        .env_remove("NO_PROXY")
+        .env_remove("no_proxy")
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let head = requests.recv().unwrap();
+    assert!(
+        head.starts_with("GET http://mirror.invalid/svc/mini.json HTTP/1.1\r\n"),
+        "{head}"
+    );
+}
+
+#[test]
+fn downloads_over_unreadable_cache() {
+    let (addr, _requests) = stub_server(vec![(200, FIXTURE)]);
+    let dir = temp_dir("unreadable");
+    // a directory where the cached puzzle should be
+    let cached = dir.join("cache/miniprint/mini-2024-03-01.json");
+    fs::create_dir_all(&cached).unwrap();
+
+    let output = fetch(&dir, &format!("[download]\nurl = \"http://{addr}\"\n"))
+        .args(["--date", "2024-03-01"])
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains("couldn't read the cached puzzle"),
+        "{stderr}"
+    );
+    assert_eq!(output.stdout, FIXTURE.as_bytes());
+}
+
+#[test]
+fn falls_back_to_cached_puzzle() {
+    let (addr, _requests) = stub_server(vec![(503, "")]);
+    let dir = temp_dir("fallback");
+    let cache = dir.join("cache").join("miniprint");
+    fs::create_dir_all(&cache).unwrap();
+    fs::write(cache.join("mini-2024-03-01.json"), FIXTURE).unwrap();
+
+    let output = fetch(
+        &dir,
+        &format!("[download]\nurl = \"http://{addr}\"\nretries = 0\n"),
+    )
+    .output()
+    .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(stderr.contains("HTTP 503"), "{stderr}");
+    assert!(
+        stderr.contains("using the latest cached puzzle"),
+        "{stderr}"
+    );
+    assert_eq!(output.stdout, FIXTURE.as_bytes());
+}
+
+#[test]
+fn downloads_daily_crossword() {
+    let daily = include_str!("fixtures/daily-2024-03-04.json");
+    let (addr, requests) = stub_server(vec![(200, daily)]);
+    let dir = temp_dir("daily");
+    let output = fetch(&dir, &format!("[download]\nurl = \"http://{addr}\"\n"))
+        .args(["--puzzle", "daily"])
+        .output()
+        .unwrap();
+    let cached = dir.join("cache/miniprint/daily-2024-03-04.json");
+    let was_cached = cached.exists();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(output.stdout, daily.as_bytes());
+    assert!(requests.recv().unwrap().starts_with("GET /daily.json "));
+    assert!(was_cached);
+}
+
+#[test]
+fn refused_download_suggests_cookie() {
+    let (addr, _requests) = stub_server(vec![(403, "")]);
+    let dir = temp_dir("refused");
+    let output = fetch(
+        &dir,
+        &format!("[download]\nurl = \"http://{addr}\"\nretries = 0\n"),
+    )
+    .args(["--puzzle", "daily", "--refresh"])
+    .output()
+    .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(5), "{stderr}");
+    assert!(stderr.contains("HTTP 403"), "{stderr}");
+    assert!(stderr.contains("nyt-s under [download]"), "{stderr}");
+}
+
+#[test]
+fn busy_server_is_worth_retrying() {
+    let (addr, _requests) = stub_server(vec![(503, "")]);
+    let dir = temp_dir("busy");
+    let output = fetch(
+        &dir,
+        &format!("[download]\nurl = \"http://{addr}\"\nretries = 0\n"),
+    )
+    .args(["--date", "2024-03-01"])
+    .output()
+    .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(4), "{stderr}");
+    assert!(stderr.contains("HTTP 503"), "{stderr}");
+}
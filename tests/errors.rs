@@ -0,0 +1,159 @@
+use std::env;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const FIXTURE: &str = include_str!("fixtures/mini-2024-03-01.json");
+const CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/config.toml");
+
+fn miniprint() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_miniprint"));
+    command.args(["--config", CONFIG]);
+    command
+}
+
+/// Run with `json` as the puzzle on stdin.
+fn print_json(json: &str, args: &[&str]) -> Output {
+    print_input(json.as_bytes(), args)
+}
+
+/// Run with `input` on stdin, as any kind of puzzle file.
+fn print_input(input: &[u8], args: &[&str]) -> Output {
+    let mut child = miniprint()
+        .args(["--input", "-", "--dry-run"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn assert_fails(output: &Output, code: i32, message: &str) {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(code), "{stderr}");
+    assert!(stderr.contains(message), "{stderr}");
+}
+
+#[test]
+fn missing_config() {
+    let output = Command::new(env!("CARGO_BIN_EXE_miniprint"))
+        .args(["--config", "/nonexistent/config.toml", "--dry-run"])
+        .output()
+        .unwrap();
+    assert_fails(&output, 3, "couldn't read /nonexistent/config.toml");
+}
+
+#[test]
+fn config_out_of_range() {
+    let path = env::temp_dir().join(format!("miniprint-{}-config.toml", std::process::id()));
+    let fixture = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/mini-2024-03-01.json"
+    );
+    let with_layout = |layout: &str| {
+        std::fs::write(&path, format!("[layout]\n{layout}\n")).unwrap();
+        Command::new(env!("CARGO_BIN_EXE_miniprint"))
+            .arg("--config")
+            .arg(&path)
+            .args(["--input", fixture, "--dry-run"])
+            .output()
+            .unwrap()
+    };
+
+    let output = with_layout("line-width = 0");
+    assert_fails(&output, 3, "the line width must be from 1 to 8");
+    let output = with_layout("grid-scale = 20.5");
+    assert_fails(&output, 3, "the scale must be from 1 to 8");
+    let output = with_layout("font-width = 0");
+    assert_fails(&output, 3, "the font width must be from 1 to 255");
+    let output = with_layout("dpi = 0");
+    assert_fails(&output, 3, "the resolution must be from 50 to 1200");
+    let output = with_layout("min-cell = 1");
+    assert_fails(&output, 3, "the smallest square must be from 8 to 256");
+    // the same as on the command line, a width in dots doesn't need quotes
+    let output = with_layout("paper = 576\ngrid-scale = 2");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn offline_without_cache() {
+    let cache = env::temp_dir().join(format!("miniprint-{}-cache", std::process::id()));
+    let output = miniprint()
+        .env("XDG_CACHE_HOME", &cache)
+        .args(["--offline", "--date", "2024-03-01", "--dry-run"])
+        .output()
+        .unwrap();
+    assert_fails(&output, 6, "no cached puzzle for 2024-03-01");
+}
+
+#[test]
+fn before_first_puzzle() {
+    let cache = env::temp_dir().join(format!("miniprint-{}-early", std::process::id()));
+    let offline = |args: &[&str]| {
+        miniprint()
+            .env("XDG_CACHE_HOME", &cache)
+            .args(["--offline", "--dry-run"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let output = offline(&["--date", "2010-01-01"]);
+    assert_fails(
+        &output,
+        2,
+        "Mini Crossword was first published on 2014-08-21",
+    );
+    // the daily goes back further
+    let output = offline(&["--puzzle", "daily", "--date", "2010-01-01"]);
+    assert_fails(&output, 6, "no cached puzzle for 2010-01-01");
+}
+
+#[test]
+fn not_a_puzzle() {
+    let output = print_json(r#"{"status": "error"}"#, &[]);
+    assert_fails(&output, 7, "isn't in the format miniprint expects");
+}
+
+#[test]
+fn puzzle_missing_parts() {
+    let fixture: serde_json::Value = serde_json::from_str(FIXTURE).unwrap();
+    let broken = |path: &str, value: serde_json::Value| {
+        let mut json = fixture.clone();
+        *json.pointer_mut(path).unwrap() = value;
+        print_json(
+            &json.to_string(),
+            &["--sections", "header,grid,clues,solution"],
+        )
+    };
+
+    let output = broken("/body", serde_json::json!([]));
+    assert_fails(&output, 7, "there's no puzzle in the body");
+    let output = broken("/body/0/clueLists/0/clues/0", serde_json::json!(99));
+    assert_fails(&output, 7, "clue 99, which doesn't exist");
+    let output = broken("/body/0/dimensions/width", serde_json::json!(0));
+    assert_fails(&output, 7, "0 squares wide");
+}
+
+#[test]
+fn corrupt_puz() {
+    let mut puz = include_bytes!("fixtures/rebus.puz").to_vec();
+    // a letter of the solution
+    puz[0x40] ^= 1;
+    let output = print_input(&puz, &[]);
+    assert_fails(&output, 7, "the file checksum is wrong");
+}
+
+#[test]
+fn broken_svg_board() {
+    let json = FIXTURE.replace("<svg", "<svgg");
+    assert_ne!(json, FIXTURE);
+    let output = print_json(&json, &["--grid", "svg"]);
+    assert_fails(&output, 8, "couldn't read the puzzle's SVG board");
+}
@@ -0,0 +1,64 @@
+use std::io::Read;
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread;
+
+const FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/mini-2024-03-01.json"
+);
+
+const CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/config.toml");
+
+fn miniprint() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_miniprint"));
+    command.args(["--config", CONFIG]);
+    command
+}
+
+#[test]
+fn prints_to_tcp_listener() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let printer = thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let mut received = Vec::new();
+        conn.read_to_end(&mut received).unwrap();
+        received
+    });
+
+    let status = miniprint()
+        .args(["--input", FIXTURE, "--output"])
+        .arg(format!("tcp:127.0.0.1:{port}"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let received = printer.join().unwrap();
+    assert!(received.starts_with(b"The NYT Mini Crossword"));
+    // GS V A 0: feed and full cut
+    assert!(received.ends_with(b"\x1dVA\x00"));
+}
+
+#[test]
+fn reports_unreachable_printer() {
+    // grab a free port, then close it again so nothing is listening there
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let output = miniprint()
+        .args(["--input", FIXTURE, "--timeout", "1", "--output"])
+        .arg(format!("tcp:127.0.0.1:{port}"))
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // printer errors exit with 10
+    assert_eq!(output.status.code(), Some(10), "{stderr}");
+    assert!(
+        stderr.contains(&format!("couldn't connect to printer at 127.0.0.1:{port}")),
+        "{stderr}"
+    );
+}
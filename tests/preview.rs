@@ -0,0 +1,75 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+const FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/mini-2024-03-01.json"
+);
+
+const CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/config.toml");
+
+fn miniprint() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_miniprint"));
+    command.args(["--config", CONFIG]);
+    command
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("miniprint-{}-{name}", std::process::id()))
+}
+
+#[test]
+fn previews_png_at_printer_width() {
+    let path = temp_path("receipt.png");
+    let status = miniprint()
+        .args(["--input", FIXTURE, "--paper", "80mm", "--output"])
+        .arg(format!("preview:{}", path.display()))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let png = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    // the IHDR chunk comes first, starting with the width and height
+    let width = u32::from_be_bytes(png[16..20].try_into().unwrap());
+    let height = u32::from_be_bytes(png[20..24].try_into().unwrap());
+    assert_eq!(width, 576);
+    assert!(height > width, "{width}x{height}");
+}
+
+#[test]
+fn previews_pdf() {
+    let path = temp_path("receipt.pdf");
+    let status = miniprint()
+        .args(["--input", FIXTURE, "--output"])
+        .arg(format!("preview:{}", path.display()))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let pdf = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(pdf.starts_with(b"%PDF-1.4\n"));
+    assert!(pdf.ends_with(b"%%EOF\n"));
+    // 384 dots at 203 dpi is 136.2 points wide
+    let pdf = String::from_utf8_lossy(&pdf);
+    assert!(pdf.contains("/MediaBox [0 0 136.20 "));
+}
+
+#[test]
+fn previews_without_a_cut() {
+    let path = temp_path("uncut.png");
+    let status = miniprint()
+        .args(["--input", FIXTURE, "--cut", "none", "--output"])
+        .arg(format!("preview:{}", path.display()))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let png = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+}
@@ -0,0 +1,52 @@
+#![cfg(all(unix, feature = "serial"))]
+
+use std::io::Read;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use serialport::{SerialPort, TTYPort};
+
+const FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/mini-2024-03-01.json"
+);
+
+const CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/config.toml");
+
+/// GS V A 0: feed and full cut
+const CUT: &[u8] = b"\x1dVA\x00";
+
+#[test]
+fn prints_to_pty() {
+    let (mut master, slave) = TTYPort::pair().unwrap();
+    master.set_timeout(Duration::from_secs(10)).unwrap();
+    let path = slave.name().unwrap();
+
+    let printer = thread::spawn(move || {
+        let mut received = Vec::new();
+        let mut buf = [0; 4096];
+        // the master never sees EOF while we hold the slave open, so stop at
+        // the cut that ends the receipt
+        while !received.ends_with(CUT) {
+            match master.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => received.extend_from_slice(&buf[..n]),
+            }
+        }
+        received
+    });
+
+    let status = Command::new(env!("CARGO_BIN_EXE_miniprint"))
+        .args(["--config", CONFIG])
+        .args(["--input", FIXTURE, "--baud-rate", "115200", "--output"])
+        .arg(format!("serial:{path}"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let received = printer.join().unwrap();
+    assert!(received.starts_with(b"The NYT Mini Crossword"));
+    assert!(received.ends_with(CUT));
+    drop(slave);
+}
@@ -0,0 +1,225 @@
+//! Compares what `--dry-run` would send to the printer against the
+//! transcripts in `tests/snapshots`. After a deliberate change to the layout,
+//! run with `UPDATE_SNAPSHOTS=1` to rewrite them and review the diff.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+const SNAPSHOTS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots");
+
+/// What `--dry-run` prints for the puzzle in `fixture`.
+fn dry_run(fixture: &str, args: &[&str]) -> String {
+    let fixtures = Path::new(FIXTURES);
+    let output = Command::new(env!("CARGO_BIN_EXE_miniprint"))
+        .arg("--config")
+        .arg(fixtures.join("config.toml"))
+        .arg("--input")
+        .arg(fixtures.join(fixture))
+        .arg("--dry-run")
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn assert_snapshot(name: &str, fixture: &str, args: &[&str]) {
+    let actual = dry_run(fixture, args);
+
+    let path = Path::new(SNAPSHOTS).join(format!("{name}.txt"));
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(SNAPSHOTS).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("couldn't read {}: {e}", path.display()));
+    if actual != expected {
+        let (line, (expected, actual)) = expected
+            .lines()
+            .chain(["<end>"])
+            .zip(actual.lines().chain(["<end>"]))
+            .enumerate()
+            .find(|(_, (expected, actual))| expected != actual)
+            .unwrap();
+        panic!(
+            "{name} differs from {} at line {}\n\
+             expected: {expected}\n  actual: {actual}\n\
+             rerun with UPDATE_SNAPSHOTS=1 if the change is intended",
+            path.display(),
+            line + 1
+        );
+    }
+}
+
+#[test]
+fn default_layout() {
+    assert_snapshot("default", "mini-2024-03-01.json", &[]);
+}
+
+#[test]
+fn wide_paper() {
+    assert_snapshot("80mm", "mini-2024-03-01.json", &["--paper", "80mm"]);
+}
+
+#[test]
+fn upside_down_solution() {
+    assert_snapshot(
+        "solution",
+        "mini-2024-03-01.json",
+        &[
+            "--sections",
+            "header,grid,clues,byline,solution",
+            "--upside-down-solution",
+        ],
+    );
+}
+
+#[test]
+fn formatted_clues() {
+    assert_snapshot("formatted", "mini-2024-03-02.json", &[]);
+}
+
+#[test]
+fn formatted_clues_on_a_western_code_page() {
+    assert_snapshot(
+        "wpc1252",
+        "mini-2024-03-02.json",
+        &["--code-pages", "pc437,wpc1252", "--italics", "marker"],
+    );
+}
+
+#[test]
+fn library_prints_the_same_receipt() {
+    use miniprint::driver::MemoryDriver;
+    use miniprint::{decode, Crossword, Receipt};
+
+    // the daily gets its own header and compact clues without being told
+    for (fixture, snapshot) in [
+        ("mini-2024-03-01.json", "default.txt"),
+        ("daily-2024-03-04.json", "daily.txt"),
+    ] {
+        let json = fs::read_to_string(Path::new(FIXTURES).join(fixture)).unwrap();
+        let crossword: Crossword = serde_json::from_str(&json).unwrap();
+        let memory = MemoryDriver::default();
+        Receipt::new(&crossword).print(memory.clone()).unwrap();
+
+        let expected = fs::read_to_string(Path::new(SNAPSHOTS).join(snapshot)).unwrap();
+        assert_eq!(decode::transcript(&memory.data()), expected, "{snapshot}");
+    }
+}
+
+#[test]
+fn library_heads_a_puz_with_its_title() {
+    use miniprint::driver::MemoryDriver;
+    use miniprint::{decode, puz, Receipt};
+
+    let data = fs::read(Path::new(FIXTURES).join("rebus.puz")).unwrap();
+    let crossword = puz::parse(&data).unwrap();
+    let memory = MemoryDriver::default();
+    Receipt::new(&crossword).print(memory.clone()).unwrap();
+
+    let transcript = decode::transcript(&memory.data());
+    assert!(transcript.starts_with("Test Puzzle\n\n["), "{transcript}");
+}
+
+#[test]
+fn daily_in_strips() {
+    assert_snapshot("daily", "daily-2024-03-04.json", &["--puzzle", "daily"]);
+}
+
+#[test]
+fn daily_on_wide_paper() {
+    // without --puzzle, the header and compact clues come from the grid size
+    assert_snapshot("daily-80mm", "daily-2024-03-04.json", &["--paper", "80mm"]);
+}
+
+#[test]
+fn daily_without_compact_clues() {
+    let daily = "daily-2024-03-04.json";
+    let spread = dry_run(daily, &["--no-compact-clues"]);
+    assert_ne!(spread, dry_run(daily, &[]));
+    assert_eq!(
+        spread,
+        dry_run(daily, &["--compact-clues", "--no-compact-clues"])
+    );
+}
+
+#[test]
+fn enlarged_grid() {
+    assert_snapshot(
+        "grid-scale",
+        "mini-2024-03-01.json",
+        &["--grid-scale", "2", "--sections", "header,grid"],
+    );
+}
+
+#[test]
+fn clues_side_by_side() {
+    assert_snapshot(
+        "two-column",
+        "mini-2024-03-02.json",
+        &[
+            "--paper",
+            "80mm",
+            "--two-column-clues",
+            "--sections",
+            "clues,byline",
+        ],
+    );
+}
+
+#[test]
+fn too_narrow_for_two_columns() {
+    // two characters to a line, five, and thirteen, where the clue numbers
+    // would take up most of each column
+    let narrow = [
+        ["--font-width", "200"],
+        ["--paper", "64"],
+        ["--paper", "160"],
+    ];
+    for narrow in narrow {
+        let args = [&narrow[..], &["--sections", "clues"]].concat();
+        let single = dry_run("mini-2024-03-01.json", &args);
+        let args = [&args[..], &["--two-column-clues"]].concat();
+        assert_eq!(dry_run("mini-2024-03-01.json", &args), single);
+    }
+}
+
+#[test]
+fn landscape() {
+    // the text in the picture is drawn with whatever fonts are installed, so
+    // only the grid goes in it here
+    assert_snapshot(
+        "landscape",
+        "mini-2024-03-01.json",
+        &["--landscape", "--sections", "grid,solution"],
+    );
+}
+
+#[test]
+fn long_landscape_in_bands() {
+    // over 40,000 rows, more than escpos can send as one image
+    let transcript = dry_run(
+        "daily-2024-03-04.json",
+        &["--landscape", "--font-width", "32"],
+    );
+    let bands = transcript.matches("[image 384x2048 ").count();
+    assert!(bands > 16, "{transcript}");
+}
+
+#[test]
+fn across_lite_file() {
+    assert_snapshot(
+        "puz",
+        "rebus.puz",
+        &["--sections", "header,grid,clues,byline,solution"],
+    );
+}